@@ -1,10 +1,16 @@
 use crate::utils::{
-    read_config, ChangeData, ChangeOpt, ListOpt, Listable, Result, WorkspaceConfig,
+    affected_pkgs, read_config, Baselines, ChangeOpt, ChangeReason, ChangedPkg, ListOpt, Listable,
+    Result, TagPrefixes, WorkspaceConfig,
 };
 
 use cargo_metadata::Metadata;
-use clap::Parser;
-use oclif::term::TERM_OUT;
+use clap::{ArgEnum, Parser};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+enum Dependents {
+    Direct,
+    Transitive,
+}
 
 /// List crates that have changed since the last tagged release
 #[derive(Debug, Parser)]
@@ -22,36 +28,65 @@ pub struct Changed {
         forbid_empty_values(true)
     )]
     since: Option<String>,
+
+    /// Also list the crates depending on the changed crates, through others with `transitive`
+    #[clap(
+        long,
+        arg_enum,
+        value_name = "MODE",
+        min_values = 0,
+        max_values = 1,
+        require_equals = true,
+        default_missing_value = "direct"
+    )]
+    include_dependents: Option<Dependents>,
 }
 
 impl Changed {
     pub fn run(self, metadata: Metadata) -> Result {
         let config: WorkspaceConfig = read_config(&metadata.workspace_metadata)?;
-        let mut since = self.since.clone();
-
-        if self.since.is_none() {
-            let change_data = ChangeData::new(&metadata, &self.change)?;
-
-            if change_data.count == "0" {
-                return Ok(TERM_OUT
-                    .write_line("Current HEAD is already released, skipping change detection")?);
+        let baselines = match &self.since {
+            Some(since) => Baselines::new(Some(since.clone())),
+            None => {
+                let prefixes = TagPrefixes::new(&config, None, None)?;
+                self.change
+                    .baselines(&metadata, &config, &prefixes, self.list.all)?
             }
+        };
 
-            since = change_data.since;
-        }
-
-        let pkgs = self.change.get_changed_pkgs(
+        let (changed, unchanged, uncommitted) = self.change.get_changed_pkgs(
             &metadata,
             &config,
-            &since,
+            &baselines,
             &self.list.groups[..],
             self.list.all,
         )?;
 
-        pkgs.0
+        let mut pkgs = changed
             .into_iter()
-            .map(|((group_name, _), pkgs)| (group_name, pkgs))
-            .collect::<Vec<_>>()
-            .list(self.list)
+            .map(|((group_name, _), pkg)| {
+                let reason = if uncommitted.contains(&pkg.name) {
+                    ChangeReason::Uncommitted
+                } else {
+                    ChangeReason::Direct
+                };
+
+                (group_name, ChangedPkg { pkg, reason })
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(dependents) = self.include_dependents {
+            let affected = affected_pkgs(
+                &metadata,
+                &pkgs,
+                unchanged,
+                &self.list.groups,
+                dependents == Dependents::Transitive,
+            );
+
+            pkgs.extend(affected);
+        }
+
+        pkgs.list(self.list)
     }
 }
@@ -0,0 +1,118 @@
+use crate::utils::{
+    info, write_changeset, Changeset as ChangesetFile, Error, ReleaseLevel, Result,
+};
+
+use cargo_metadata::Metadata;
+use clap::{ArgEnum, Parser};
+use dialoguer::{theme::ColorfulTheme, Input, MultiSelect, Select};
+use oclif::term::TERM_ERR;
+
+use std::{collections::BTreeMap as Map, process::exit};
+
+/// Declare the intended releases of crates in a changeset
+#[derive(Debug, Parser)]
+pub struct Changeset {
+    /// Crates to release, optionally suffixed with their own level (`<crate>:<level>`)
+    #[clap(value_name = "crate")]
+    crates: Vec<String>,
+
+    /// Release level for crates given without one
+    #[clap(long, arg_enum, value_name = "level")]
+    level: Option<ReleaseLevel>,
+
+    /// Summary of the changes
+    #[clap(short, long, value_name = "summary", forbid_empty_values(true))]
+    message: Option<String>,
+
+    /// Name of the changeset file, defaults to a generated id
+    #[clap(long, value_name = "id", forbid_empty_values(true))]
+    name: Option<String>,
+}
+
+impl Changeset {
+    pub fn run(self, metadata: Metadata) -> Result {
+        let theme = ColorfulTheme::default();
+
+        let mut names = metadata
+            .packages
+            .iter()
+            .map(|p| p.name.clone())
+            .collect::<Vec<_>>();
+        names.sort();
+
+        let crates = if self.crates.is_empty() {
+            let selected = MultiSelect::with_theme(&theme)
+                .with_prompt("Crates to release")
+                .items(&names)
+                .interact_on_opt(&TERM_ERR)?;
+
+            match selected {
+                Some(selected) if !selected.is_empty() => selected
+                    .into_iter()
+                    .map(|i| (names[i].clone(), None))
+                    .collect(),
+                _ => exit(0),
+            }
+        } else {
+            self.crates
+                .iter()
+                .map(|x| match x.split_once(':') {
+                    Some((name, level)) => ReleaseLevel::from_str(level, true)
+                        .map(|level| (name.to_string(), Some(level)))
+                        .map_err(|msg| Error::InvalidChangeset {
+                            path: x.clone(),
+                            msg,
+                        }),
+                    None => Ok((x.clone(), None)),
+                })
+                .collect::<Result<Vec<_>>>()?
+        };
+
+        if let Some((name, _)) = crates.iter().find(|(name, _)| !names.contains(name)) {
+            return Err(Error::PackageNotFound { id: name.clone() });
+        }
+
+        let level = match self.level {
+            Some(level) => level,
+            None if crates.iter().all(|(_, level)| level.is_some()) => ReleaseLevel::Patch,
+            None => {
+                let levels = ReleaseLevel::value_variants();
+                let items = levels.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+
+                let selected = Select::with_theme(&theme)
+                    .with_prompt("Release level")
+                    .items(&items)
+                    .default(0)
+                    .interact_on(&TERM_ERR)?;
+
+                levels[selected]
+            }
+        };
+
+        let summary = match self.message {
+            Some(message) => message,
+            None => Input::with_theme(&theme)
+                .with_prompt("Summary of the changes")
+                .interact_on(&TERM_ERR)?,
+        };
+
+        let changeset = ChangesetFile {
+            releases: crates
+                .into_iter()
+                .map(|(name, x)| (name, x.unwrap_or(level)))
+                .collect::<Map<_, _>>(),
+            summary,
+        };
+
+        let path = write_changeset(&metadata.workspace_root, self.name.as_deref(), &changeset)?;
+
+        info!(
+            "created",
+            path.strip_prefix(&metadata.workspace_root)
+                .unwrap_or(&path)
+                .display()
+        );
+
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
 use crate::utils::{
-    cargo, change_versions, info, Error, ManifestDiscriminant, Result, INTERNAL_ERR,
+    cargo, change_versions, get_dry_run, info, read_file, write_file, Error, ManifestDiscriminant,
+    Result, INTERNAL_ERR,
 };
 
 use cargo_metadata::Metadata;
@@ -8,10 +9,7 @@ use dialoguer::{theme::ColorfulTheme, Input, Select};
 use oclif::term::TERM_ERR;
 use semver::Version;
 
-use std::{
-    collections::{BTreeMap as Map, HashSet},
-    fs,
-};
+use std::collections::{BTreeMap as Map, HashSet};
 
 #[derive(Debug, Clone, ArgEnum)]
 enum Edition {
@@ -108,6 +106,11 @@ impl Create {
 
         let created = cargo(&metadata.workspace_root, &args, &[])?;
 
+        // The crate was not created, so there is no manifest to edit
+        if get_dry_run() {
+            return Ok(());
+        }
+
         if !created.1.contains("Created") {
             return Err(Error::Create);
         }
@@ -117,10 +120,10 @@ impl Create {
 
         versions.insert(name.clone(), Version::parse("0.0.0").expect(INTERNAL_ERR));
 
-        fs::write(
+        write_file(
             &manifest,
             change_versions(
-                fs::read_to_string(&manifest)?,
+                read_file(&manifest)?,
                 &name,
                 &versions,
                 ManifestDiscriminant::Package,
@@ -0,0 +1,36 @@
+use crate::utils::{related_pkgs, DepKind, ListOpt, Listable, Result};
+use cargo_metadata::Metadata;
+use clap::Parser;
+
+/// List the crates in the workspace that a crate depends on
+#[derive(Debug, Parser)]
+pub struct Dependencies {
+    #[clap(flatten)]
+    list: ListOpt,
+
+    /// Also list the dependencies of those crates, and so on
+    #[clap(long)]
+    transitive: bool,
+
+    /// Only follow these kinds of dependencies
+    #[clap(
+        long,
+        arg_enum,
+        multiple_occurrences = true,
+        use_value_delimiter = true,
+        number_of_values = 1
+    )]
+    kind: Vec<DepKind>,
+
+    /// Name of the crate
+    name: String,
+}
+
+impl Dependencies {
+    pub fn run(self, metadata: Metadata) -> Result {
+        related_pkgs(&metadata, &self.list, &self.name, |graph| {
+            graph.dependencies(&self.name, &self.kind, self.transitive)
+        })?
+        .list(self.list)
+    }
+}
@@ -0,0 +1,36 @@
+use crate::utils::{related_pkgs, DepKind, ListOpt, Listable, Result};
+use cargo_metadata::Metadata;
+use clap::Parser;
+
+/// List the crates in the workspace that depend on a crate
+#[derive(Debug, Parser)]
+pub struct Dependents {
+    #[clap(flatten)]
+    list: ListOpt,
+
+    /// Also list the crates depending on them, and so on
+    #[clap(long)]
+    transitive: bool,
+
+    /// Only follow these kinds of dependencies
+    #[clap(
+        long,
+        arg_enum,
+        multiple_occurrences = true,
+        use_value_delimiter = true,
+        number_of_values = 1
+    )]
+    kind: Vec<DepKind>,
+
+    /// Name of the crate
+    name: String,
+}
+
+impl Dependents {
+    pub fn run(self, metadata: Metadata) -> Result {
+        related_pkgs(&metadata, &self.list, &self.name, |graph| {
+            graph.dependents(&self.name, &self.kind, self.transitive)
+        })?
+        .list(self.list)
+    }
+}
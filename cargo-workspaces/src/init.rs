@@ -1,11 +1,11 @@
-use crate::utils::{info, Error, Result};
+use crate::utils::{info, write_file, Error, Result};
 
 use cargo_metadata::MetadataCommand;
 use clap::Parser;
 use dunce::canonicalize;
 use glob::glob;
 
-use std::{collections::HashSet, fs::write, path::PathBuf};
+use std::{collections::HashSet, path::PathBuf};
 
 /// Initializes a new cargo workspace
 #[derive(Debug, Parser)]
@@ -65,7 +65,7 @@ impl Init {
 
         content.push_str("]\n");
 
-        write(cargo_toml, content)?;
+        write_file(cargo_toml, content)?;
 
         info!("initialized", self.path.display());
         Ok(())
@@ -1,4 +1,6 @@
-use crate::utils::{get_group_packages, read_config, ListOpt, Listable, Result, WorkspaceConfig};
+use crate::utils::{
+    get_group_packages, read_config, Graph, GraphFormat, ListOpt, Listable, Result, WorkspaceConfig,
+};
 use cargo_metadata::Metadata;
 use clap::Parser;
 
@@ -8,21 +10,37 @@ use clap::Parser;
 pub struct List {
     #[clap(flatten)]
     list: ListOpt,
+
+    /// Show the dependencies between the crates as a graph
+    #[clap(
+        long,
+        arg_enum,
+        value_name = "FORMAT",
+        conflicts_with_all = &["long", "json"]
+    )]
+    graph: Option<GraphFormat>,
 }
 
 impl List {
     pub fn run(self, metadata: Metadata) -> Result {
         let config: WorkspaceConfig = read_config(&metadata.workspace_metadata)?;
 
-        let workspace_groups = get_group_packages(&metadata, &config, self.list.all)?;
+        // The graph shows every crate, marking the private ones instead of hiding them
+        let all = self.list.all || self.graph.is_some();
+        let workspace_groups = get_group_packages(&metadata, &config, all)?;
 
-        workspace_groups
+        let pkgs = workspace_groups
             .into_iter()
             .map(|((group_name, _), pkgs)| (group_name, pkgs))
             .filter(|(group_name, _)| {
                 self.list.groups.is_empty() || self.list.groups.contains(group_name)
             })
-            .collect::<Vec<_>>()
-            .list(self.list)
+            .collect::<Vec<_>>();
+
+        if let Some(format) = self.graph {
+            return Graph::new(&metadata, &pkgs).print(format);
+        }
+
+        pkgs.list(self.list)
     }
 }
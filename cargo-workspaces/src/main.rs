@@ -1,10 +1,14 @@
 mod changed;
+mod changeset;
 mod create;
+mod dependencies;
+mod dependents;
 mod exec;
 mod init;
 mod list;
 mod publish;
 mod rename;
+mod run;
 mod version;
 
 mod utils;
@@ -18,9 +22,13 @@ enum Subcommand {
     // TODO: add
     List(list::List),
     Changed(changed::Changed),
+    Changeset(changeset::Changeset),
+    Dependents(dependents::Dependents),
+    Dependencies(dependencies::Dependencies),
     Version(version::Version),
     Publish(publish::Publish),
     Exec(exec::Exec),
+    Run(run::Run),
     Create(create::Create),
     Rename(rename::Rename),
     Init(init::Init),
@@ -41,6 +49,20 @@ struct Opt {
     #[clap(short)]
     verbose: bool,
 
+    /// Show the changes that would be made without making them
+    #[clap(long, global = true)]
+    dry_run: bool,
+
+    /// Output format of the messages, `json` writes events to stdout as JSON lines
+    #[clap(
+        long,
+        global = true,
+        arg_enum,
+        value_name = "FMT",
+        default_value = "human"
+    )]
+    message_format: utils::MessageFormat,
+
     #[clap(subcommand)]
     subcommand: Subcommand,
 }
@@ -61,6 +83,16 @@ fn main() {
         utils::set_debug();
     }
 
+    if opt.message_format == utils::MessageFormat::Json {
+        utils::set_json_messages();
+    }
+
+    let dry_run = opt.dry_run;
+
+    if dry_run {
+        utils::set_dry_run();
+    }
+
     let result = if let Subcommand::Init(ref init) = opt.subcommand {
         init.run()
     } else {
@@ -79,15 +111,35 @@ fn main() {
         match opt.subcommand {
             Subcommand::List(x) => x.run(metadata),
             Subcommand::Changed(x) => x.run(metadata),
+            Subcommand::Changeset(x) => x.run(metadata),
+            Subcommand::Dependents(x) => x.run(metadata),
+            Subcommand::Dependencies(x) => x.run(metadata),
             Subcommand::Version(x) => x.run(metadata),
             Subcommand::Publish(x) => x.run(metadata),
             Subcommand::Exec(x) => x.run(metadata),
+            Subcommand::Run(x) => x.run(metadata),
             Subcommand::Create(x) => x.run(metadata),
             Subcommand::Rename(x) => x.run(metadata),
             _ => unreachable!(),
         }
     };
 
+    let result = result.and_then(|_| {
+        if dry_run {
+            utils::dry_run_summary()
+        } else {
+            Ok(())
+        }
+    });
+
+    if let Err(err) = &result {
+        let _ = utils::Event::Error {
+            code: err.code(),
+            message: err.to_string(),
+        }
+        .emit();
+    }
+
     finish(result)
 }
 
@@ -96,6 +148,19 @@ fn set_handlers() {
     ctrlc::set_handler(move || {
         let term = dialoguer::console::Term::stdout();
         let _ = term.show_cursor();
+
+        // Undo the changes of an interrupted versioning
+        match utils::rollback_journal() {
+            Ok(None) => {}
+            Ok(Some(restored)) => {
+                eprintln!("interrupted, restored {} file(s)", restored.len());
+                std::process::exit(130);
+            }
+            Err(err) => {
+                eprintln!("interrupted, unable to restore files: {}", err);
+                std::process::exit(130);
+            }
+        }
     })
     .expect("Error setting Ctrl-C handler");
 }
@@ -1,11 +1,21 @@
 use crate::utils::{
-    cargo, cargo_config_get, check_index, dag, info, is_published, read_config, Error, Result,
-    VersionOpt, INTERNAL_ERR,
+    cargo, cargo_config_get, cargo_quiet, check_index, dag, get_dry_run, get_group_packages, git,
+    info, is_published, read_config, validate_jobs, would_run, CrateState, Error, Event,
+    LocalRegistry, PublishState, PublishStatus, RegistryIndex, Result, Step, VersionOpt,
+    WorkspaceConfig, INTERNAL_ERR,
 };
-use cargo_metadata::Metadata;
+use cargo_metadata::{CargoOpt, DependencyKind, Metadata, MetadataCommand, Package};
 use clap::Parser;
-use crates_index::Index;
 use indexmap::IndexSet as Set;
+use oclif::term::TERM_ERR;
+use semver::Version;
+
+use std::{
+    collections::BTreeMap as Map,
+    path::PathBuf,
+    sync::{mpsc, Mutex},
+    thread,
+};
 
 /// Publish crates in the project
 #[derive(Debug, Parser)]
@@ -38,22 +48,290 @@ pub struct Publish {
     /// The Cargo registry to use for publishing
     #[clap(long, forbid_empty_values(true))]
     registry: Option<String>,
+
+    /// Number of crates to publish concurrently, as soon as their dependencies are available
+    #[clap(short, long, default_value = "1", value_name = "N", validator = validate_jobs)]
+    jobs: usize,
+
+    /// Package the crates into a local registry in this directory instead of uploading them
+    #[clap(long, value_name = "DIR", conflicts_with_all = &["registry", "token"])]
+    local_registry: Option<PathBuf>,
+
+    /// Do not package and verify every crate before uploading any of them
+    #[clap(long)]
+    no_preflight: bool,
+
+    /// Continue an unfinished release without versioning again
+    #[clap(long, conflicts_with = "from-git")]
+    resume: bool,
 }
 
 impl Publish {
-    pub fn run(self, metadata: Metadata) -> Result {
+    pub fn run(mut self, metadata: Metadata) -> Result {
         let config = read_config(&metadata.workspace_metadata)?;
 
-        let mut versions = None;
+        // Packaging into a local registry only rehearses the release, which is not tagged or pushed
+        if self.local_registry.is_some() {
+            self.version.git.no_git_tag = true;
+            self.version.git.no_git_push = true;
+        }
+
         let branch = self
             .version
             .git
             .validate(&metadata.workspace_root, &config)?;
 
+        let state_path = PublishState::path(&metadata);
+
+        let mut state = if self.resume {
+            let mut state = PublishState::read(&state_path)?.ok_or(Error::NoPublishState)?;
+
+            // Tags rolled back after a failure are created again
+            let (_, existing, _) = git(&metadata.workspace_root, &["tag", "-l"])?;
+            state.forget_missing_tags(&existing.lines().collect::<Vec<_>>());
+
+            info!(
+                "resuming",
+                format!(
+                    "{} of {} crates left to publish",
+                    state
+                        .crates
+                        .iter()
+                        .filter(|c| c.status == PublishStatus::Pending)
+                        .count(),
+                    state.crates.len()
+                )
+            );
+
+            state
+        } else {
+            // Starting over would lose track of the crates already published
+            if state_path.exists() {
+                return Err(Error::PublishStateExists(state_path.to_string()));
+            }
+
+            let state = self.plan(&metadata, &config)?;
+            state.save(&state_path)?;
+            state
+        };
+
+        // Versioning changed the requirements between the crates, which are packaged as written
+        let metadata = if self.resume || state.versions.is_empty() || get_dry_run() {
+            metadata
+        } else {
+            MetadataCommand::new()
+                .manifest_path(metadata.workspace_root.join("Cargo.toml"))
+                .features(CargoOpt::AllFeatures)
+                .no_deps()
+                .exec()?
+        };
+
+        let local_registry = self
+            .local_registry
+            .as_ref()
+            .map(|dir| LocalRegistry::new(dir, &metadata.target_directory))
+            .transpose()?;
+
+        let released = state
+            .crates
+            .iter()
+            .map(|c| (c.name.clone(), c.version.clone()))
+            .collect::<Map<_, _>>();
+
+        let pkgs = state
+            .crates
+            .iter()
+            .map(|c| {
+                metadata
+                    .packages
+                    .iter()
+                    .find(|p| p.manifest_path == c.manifest_path)
+                    .ok_or_else(|| Error::PackageNotFound { id: c.name.clone() })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        // Workspace crates that need to be in the index before publishing each crate
+        let deps = pkgs
+            .iter()
+            .map(|pkg| {
+                pkg.dependencies
+                    .iter()
+                    .filter(|d| matches!(d.kind, DependencyKind::Normal | DependencyKind::Build))
+                    .filter_map(|d| pkgs.iter().position(|p| p.name == d.name))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        // Package and verify all the crates first, so that none are uploaded if one of them fails
+        let verified = !self.no_preflight && local_registry.is_none();
+
+        if verified {
+            if let Err(err) = self.preflight(&metadata, &pkgs, &state, &released) {
+                if !get_dry_run() {
+                    info!("note", "run `publish --resume` to continue this release");
+                }
+
+                return Err(err);
+            }
+        }
+
+        // Each registry index is opened once and shared by the crates published to it
+        let indexes = match local_registry {
+            Some(_) => Map::new(),
+            None => registry_indexes(&metadata, &pkgs, &state)?,
+        };
+
+        let mut started = Set::new();
+        let mut published = Set::new();
+        let mut running = 0;
+        let mut failure = None;
+
+        // Crates published before resuming only need their tags, if they were not created yet
+        for (i, pkg) in pkgs.iter().enumerate() {
+            if state.crates[i].status == PublishStatus::Pending {
+                continue;
+            }
+
+            if state.crates[i].status == PublishStatus::Published {
+                self.tag(&metadata, &config, pkg, &mut state.crates[i])?;
+                state.save(&state_path)?;
+            }
+
+            started.insert(i);
+            published.insert(i);
+        }
+
+        thread::scope(|scope| -> Result {
+            let (tx, rx) = mpsc::channel();
+
+            loop {
+                while failure.is_none() && running < self.jobs {
+                    let next = (0..pkgs.len()).find(|i| {
+                        !started.contains(i) && deps[*i].iter().all(|dep| published.contains(dep))
+                    });
+
+                    let i = match next {
+                        Some(i) => i,
+                        None => break,
+                    };
+
+                    let pkg = pkgs[i];
+                    let version = state.crates[i].version.clone();
+                    let tx = tx.clone();
+                    let this = &self;
+                    let metadata = &metadata;
+                    let local_registry = local_registry.as_ref();
+                    let released = &released;
+                    let indexes = &indexes;
+
+                    started.insert(i);
+                    running += 1;
+
+                    scope.spawn(move || {
+                        let result = match local_registry {
+                            Some(registry) => this
+                                .package(metadata, pkg, &version, registry, released)
+                                .and_then(|_| {
+                                    Event::Published {
+                                        name: &pkg.name,
+                                        version: &version,
+                                    }
+                                    .emit()
+                                }),
+                            None => {
+                                let index = indexes.get(&registry(pkg)).expect(INTERNAL_ERR);
+                                this.publish(metadata, pkg, &version, index, verified)
+                            }
+                        };
+
+                        let _ = tx.send((i, result));
+                    });
+                }
+
+                if running == 0 {
+                    break;
+                }
+
+                let (i, result) = rx.recv().expect(INTERNAL_ERR);
+                running -= 1;
+
+                if let Err(err) = result {
+                    // Let the crates being published finish but do not start new ones
+                    failure.get_or_insert(err);
+                    continue;
+                }
+
+                state.crates[i].status = PublishStatus::Published;
+                state.save(&state_path)?;
+
+                self.tag(&metadata, &config, pkgs[i], &mut state.crates[i])?;
+                state.save(&state_path)?;
+
+                published.insert(i);
+            }
+
+            Ok(())
+        })?;
+
+        if let Some(err) = failure {
+            if !get_dry_run() {
+                info!("note", "run `publish --resume` to continue this release");
+            }
+
+            return Err(err);
+        }
+
+        if state.step < Step::Published {
+            state.step = Step::Published;
+            state.save(&state_path)?;
+        }
+
+        if state.step < Step::Tagged {
+            if let Some(new_version) = &state.version {
+                let new_versions = get_group_packages(&metadata, &config, true)?
+                    .into_iter()
+                    .filter_map(|(_, pkg)| {
+                        let version = state.versions.get(&pkg.name)?.clone();
+                        Some((pkg.name.clone(), (pkg, version)))
+                    })
+                    .collect();
+
+                state.tag = self.version.git.global_tag(
+                    &metadata.workspace_root,
+                    new_version,
+                    &new_versions,
+                    &config,
+                )?;
+            }
+
+            state.step = Step::Tagged;
+            state.save(&state_path)?;
+        }
+
+        // The commit and tags are only pushed when the crates were versioned
+        if state.step < Step::Pushed && !state.versions.is_empty() {
+            self.version
+                .git
+                .push(&metadata.workspace_root, &branch, &state.tags())?;
+
+            state.step = Step::Pushed;
+            state.save(&state_path)?;
+        }
+
+        PublishState::remove(&state_path)?;
+
+        info!("success", "ok");
+        Ok(())
+    }
+
+    /// Versions the crates, unless publishing from git, and plans the order to publish them in
+    fn plan(&self, metadata: &Metadata, config: &WorkspaceConfig) -> Result<PublishState> {
+        let mut state = PublishState::default();
+
         let pkgs = if !self.from_git {
             let mut new_versions = vec![];
             if let Some((new_version, _new_versions)) =
-                self.version.do_versioning(&metadata, &config)?
+                self.version.do_versioning(metadata, config)?
             {
                 for (_, (pkg, ver)) in &_new_versions {
                     new_versions.push((
@@ -64,9 +342,18 @@ impl Publish {
                             .expect(INTERNAL_ERR),
                         ver.clone(),
                     ));
+                    state.versions.insert(pkg.name.clone(), ver.clone());
                 }
-                versions = Some((new_version, _new_versions));
+                state.version = new_version;
             }
+
+            // Versioning is undone when it fails, so it is complete once the state is saved
+            state.step = if self.version.git.no_git || self.version.git.no_git_commit {
+                Step::Versioned
+            } else {
+                Step::Committed
+            };
+
             new_versions
         } else {
             metadata
@@ -78,105 +365,252 @@ impl Publish {
 
         let (names, visited) = dag(&pkgs);
 
-        // Filter out private packages
-        let visited = visited
-            .into_iter()
-            .filter(|x| {
-                if let Some((pkg, _)) = pkgs.iter().find(|(p, _)| p.manifest_path == **x) {
-                    return pkg.publish.is_none()
-                        || !pkg.publish.as_ref().expect(INTERNAL_ERR).is_empty();
-                }
+        for p in visited {
+            let (pkg, version) = names.get(&p).expect(INTERNAL_ERR);
 
-                false
-            })
-            .collect::<Set<_>>();
-
-        let mut tags = vec![];
-        for p in &visited {
-            let (pkg, version) = names.get(p).expect(INTERNAL_ERR);
-            let version = version.to_string();
-
-            'publish: {
-                let name = pkg.name.clone();
-                let mut args = vec!["publish"];
-
-                let name_ver = format!("{} v{}", name, version);
-
-                let mut index = if let Some(publish) =
-                    pkg.publish.as_deref().and_then(|x| x.get(0)).as_deref()
-                {
-                    let registry_url = cargo_config_get(
-                        &metadata.workspace_root,
-                        &format!("registries.{}.index", publish),
-                    )?;
-                    Index::from_url(&format!("registry+{}", registry_url))?
-                } else {
-                    Index::new_cargo_default()?
-                };
-
-                if is_published(&mut index, &name, &version)? {
-                    info!("already published", name_ver);
-                    break 'publish;
-                }
+            // Filter out private packages
+            if pkg.publish.as_ref().is_some_and(Vec::is_empty) {
+                continue;
+            }
 
-                if self.no_verify {
-                    args.push("--no-verify");
-                }
+            state.crates.push(CrateState {
+                name: pkg.name.clone(),
+                version: (*version).clone(),
+                manifest_path: p.clone(),
+                status: PublishStatus::Pending,
+                tag: None,
+            });
+        }
 
-                if self.allow_dirty {
-                    args.push("--allow-dirty");
-                }
+        Ok(state)
+    }
 
-                if let Some(ref registry) = self.registry {
-                    args.push("--registry");
-                    args.push(registry);
-                }
+    fn tag(
+        &self,
+        metadata: &Metadata,
+        config: &WorkspaceConfig,
+        pkg: &Package,
+        c: &mut CrateState,
+    ) -> Result {
+        c.tag = self.version.git.individual_tag(
+            &metadata.workspace_root,
+            &pkg.name,
+            pkg.publish.as_ref().map_or(false, Vec::is_empty),
+            &c.version.to_string(),
+            config,
+        )?;
+        c.status = PublishStatus::Tagged;
 
-                if let Some(ref token) = self.token {
-                    args.push("--token");
-                    args.push(token);
-                }
+        Ok(())
+    }
 
-                args.push("--manifest-path");
-                args.push(p.as_str());
+    fn publish(
+        &self,
+        metadata: &Metadata,
+        pkg: &Package,
+        version: &Version,
+        index: &Mutex<RegistryIndex>,
+        verified: bool,
+    ) -> Result {
+        let name = pkg.name.clone();
+        let ver = version.to_string();
+        let mut args = vec!["publish"];
 
-                let (_, stderr) = cargo(&metadata.workspace_root, &args, &[])?;
+        let name_ver = format!("{} v{}", name, ver);
 
-                if !stderr.contains("Uploading") || stderr.contains("error:") {
-                    return Err(Error::Publish(name));
-                }
+        if is_published(index, &name, &ver)? {
+            info!("already published", name_ver);
+            return Ok(());
+        }
 
-                check_index(&mut index, &name, &version)?;
+        info!("publishing", name_ver);
 
-                info!("published", name_ver);
-            }
+        // Crates verified in the pre-flight do not need to be built again
+        if self.no_verify || verified {
+            args.push("--no-verify");
+        }
 
-            if let Some(tag) = self.version.git.individual_tag(
-                &metadata.workspace_root,
-                &pkg.name,
-                pkg.publish.as_ref().map_or(false, Vec::is_empty),
-                &version,
-                &config,
-            )? {
-                tags.push(tag)
+        if self.allow_dirty {
+            args.push("--allow-dirty");
+        }
+
+        if let Some(ref registry) = self.registry {
+            args.push("--registry");
+            args.push(registry);
+        }
+
+        if let Some(ref token) = self.token {
+            args.push("--token");
+            args.push(token);
+        }
+
+        args.push("--manifest-path");
+        args.push(pkg.manifest_path.as_str());
+
+        // Keep the output of concurrent crates from interleaving
+        let (_, stderr) = if self.jobs > 1 {
+            cargo_quiet(&metadata.workspace_root, &args, &[])?
+        } else {
+            cargo(&metadata.workspace_root, &args, &[])?
+        };
+
+        if get_dry_run() {
+            return Ok(());
+        }
+
+        if !stderr.contains("Uploading") || stderr.contains("error:") {
+            if self.jobs > 1 {
+                TERM_ERR.write_line(&stderr)?;
             }
+
+            return Err(Error::Publish(name));
         }
 
-        if let Some((Some(new_version), new_versions)) = versions {
-            if let Some(tag) = self.version.git.global_tag(
-                &metadata.workspace_root,
-                &new_version,
-                &new_versions,
-            )? {
-                tags.push(tag)
+        check_index(index, &name, &ver)?;
+
+        info!("published", name_ver);
+
+        Event::Published {
+            name: &pkg.name,
+            version,
+        }
+        .emit()?;
+
+        Ok(())
+    }
+
+    /// Packages and verifies the crates left to publish, resolving their workspace dependencies
+    /// from a temporary registry
+    fn preflight(
+        &self,
+        metadata: &Metadata,
+        pkgs: &[&Package],
+        state: &PublishState,
+        released: &Map<String, Version>,
+    ) -> Result {
+        let overlay = LocalRegistry::overlay(&metadata.target_directory)?;
+
+        let pending = state
+            .crates
+            .iter()
+            .zip(pkgs)
+            .filter(|(c, _)| c.status == PublishStatus::Pending)
+            .collect::<Vec<_>>();
+
+        if pending.is_empty() {
+            return Ok(());
+        }
+
+        info!("pre-flight", format!("packaging {} crates", pending.len()));
+
+        for (c, pkg) in pending {
+            self.package(metadata, pkg, &c.version, &overlay, released)?;
+        }
+
+        info!("pre-flight", "ok");
+
+        Ok(())
+    }
+
+    fn package(
+        &self,
+        metadata: &Metadata,
+        pkg: &Package,
+        version: &Version,
+        registry: &LocalRegistry,
+        released: &Map<String, Version>,
+    ) -> Result {
+        let name_ver = format!("{} v{}", pkg.name, version);
+
+        if registry.is_published(&pkg.name, version)? {
+            info!("already published", name_ver);
+            return Ok(());
+        }
+
+        info!("packaging", name_ver);
+
+        let patches = registry.patches(pkg, released)?;
+        let mut args = vec!["package"];
+
+        if self.no_verify {
+            args.push("--no-verify");
+        }
+
+        if self.allow_dirty {
+            args.push("--allow-dirty");
+        }
+
+        // Resolve the workspace dependencies from the registry instead of crates.io
+        for patch in &patches {
+            args.push("--config");
+            args.push(patch);
+        }
+
+        args.push("--manifest-path");
+        args.push(pkg.manifest_path.as_str());
+
+        if would_run("cargo", &args)? {
+            return Ok(());
+        }
+
+        let (_, stderr) = if self.jobs > 1 {
+            cargo_quiet(&metadata.workspace_root, &args, &[])?
+        } else {
+            cargo(&metadata.workspace_root, &args, &[])?
+        };
+
+        if !stderr.contains("Packaged") || stderr.contains("error:") {
+            if self.jobs > 1 {
+                TERM_ERR.write_line(&stderr)?;
             }
 
-            self.version
-                .git
-                .push(&metadata.workspace_root, &branch, &tags)?;
+            return Err(Error::Package(pkg.name.clone()));
         }
 
-        info!("success", "ok");
+        let crate_file = metadata
+            .target_directory
+            .join("package")
+            .join(format!("{}-{}.crate", pkg.name, version));
+
+        registry.add(pkg, version, crate_file.as_std_path())?;
+
+        info!("packaged", name_ver);
+
         Ok(())
     }
 }
+
+/// Name of the registry a crate is published to, `None` for crates.io
+fn registry(pkg: &Package) -> Option<String> {
+    pkg.publish.as_ref().and_then(|x| x.get(0)).cloned()
+}
+
+/// Opens and fetches the index of every registry the crates left to publish go to
+fn registry_indexes(
+    metadata: &Metadata,
+    pkgs: &[&Package],
+    state: &PublishState,
+) -> Result<Map<Option<String>, Mutex<RegistryIndex>>> {
+    let mut indexes = Map::new();
+
+    for (c, pkg) in state.crates.iter().zip(pkgs) {
+        let name = registry(pkg);
+
+        if c.status != PublishStatus::Pending || indexes.contains_key(&name) {
+            continue;
+        }
+
+        let mut index = match &name {
+            Some(name) => RegistryIndex::from_url(&cargo_config_get(
+                &metadata.workspace_root,
+                &format!("registries.{}.index", name),
+            )?)?,
+            None => RegistryIndex::crates_io(&metadata.workspace_root)?,
+        };
+
+        index.update()?;
+        indexes.insert(name, Mutex::new(index));
+    }
+
+    Ok(indexes)
+}
@@ -1,11 +1,11 @@
 use crate::utils::{
-    get_group_packages, read_config, rename_packages, validate_value_containing_name, Error,
-    GroupName, WorkspaceConfig,
+    get_group_packages, read_config, read_file, rename_packages, validate_value_containing_name,
+    write_file, Error, GroupName, WorkspaceConfig,
 };
 use cargo_metadata::Metadata;
 use clap::Parser;
 use globset::{Error as GlobsetError, Glob};
-use std::{collections::BTreeMap as Map, fs};
+use std::collections::BTreeMap as Map;
 
 /// Rename crates in the project
 #[derive(Debug, Parser)]
@@ -91,27 +91,17 @@ impl Rename {
                     .map(|p| &p.name)
                     .any(|p| rename_map.contains_key(p))
             {
-                fs::write(
+                write_file(
                     &pkg.manifest_path,
-                    format!(
-                        "{}\n",
-                        rename_packages(
-                            fs::read_to_string(&pkg.manifest_path)?,
-                            &pkg.name,
-                            &rename_map,
-                        )?
-                    ),
+                    rename_packages(read_file(&pkg.manifest_path)?, &pkg.name, &rename_map)?,
                 )?;
             }
         }
 
         let workspace_root = metadata.workspace_root.join("Cargo.toml");
-        fs::write(
+        write_file(
             &workspace_root,
-            format!(
-                "{}\n",
-                rename_packages(fs::read_to_string(&workspace_root)?, "", &rename_map)?
-            ),
+            rename_packages(read_file(&workspace_root)?, "", &rename_map)?,
         )?;
 
         Ok(())
@@ -0,0 +1,67 @@
+use crate::utils::{read_config, Error, ExecOpt, PackageConfig, Result, WorkspaceConfig};
+use cargo_metadata::Metadata;
+use clap::Parser;
+
+/// Run a named script in each crate that defines it
+#[derive(Debug, Parser)]
+#[clap(trailing_var_arg(true))]
+pub struct Run {
+    #[clap(flatten)]
+    exec: ExecOpt,
+
+    /// Name of the script
+    #[clap(forbid_empty_values(true))]
+    script: String,
+
+    /// Arguments passed to the script
+    args: Vec<String>,
+}
+
+impl Run {
+    pub fn run(&self, metadata: Metadata) -> Result {
+        let config: WorkspaceConfig = read_config(&metadata.workspace_metadata)?;
+        let default = config.scripts.get(&self.script);
+
+        if default.is_none() {
+            let mut defined = false;
+
+            for pkg in &metadata.packages {
+                let pkg_config: PackageConfig = read_config(&pkg.metadata)?;
+                defined |= pkg_config.scripts.contains_key(&self.script);
+            }
+
+            if !defined {
+                return Err(Error::ScriptNotFound(self.script.clone()));
+            }
+        }
+
+        self.exec.run(&metadata, &config, |pkg| {
+            pkg.config
+                .scripts
+                .get(&self.script)
+                .or(default)
+                .map(|script| self.command(script))
+        })
+    }
+
+    /// Runs the script with the shell, appending the extra arguments
+    fn command(&self, script: &str) -> Vec<String> {
+        if cfg!(windows) {
+            let mut args = vec!["cmd".to_string(), "/C".to_string(), script.to_string()];
+            args.extend(self.args.iter().cloned());
+            return args;
+        }
+
+        let mut args = vec!["sh".to_string(), "-c".to_string()];
+
+        if self.args.is_empty() {
+            args.push(script.to_string());
+        } else {
+            args.push(format!("{} \"$@\"", script));
+            args.push(self.script.clone());
+            args.extend(self.args.iter().cloned());
+        }
+
+        args
+    }
+}
@@ -1,91 +1,54 @@
-use crate::utils::{debug, get_debug, info, Error, Result, INTERNAL_ERR};
+use crate::utils::{debug, get_debug, info, would_run, Error, Result, INTERNAL_ERR};
 
 use camino::Utf8Path;
-use crates_index::Index;
-use lazy_static::lazy_static;
+use crates_index::{Crate, Index};
 use oclif::term::TERM_ERR;
-use regex::{Captures, Regex};
 use semver::{Version, VersionReq};
+use toml_edit::{value, Document, InlineTable, Item, TableLike, Value};
 
 use std::{
-    cell::RefCell,
     collections::{BTreeMap as Map, HashSet},
-    io::{BufRead, BufReader},
+    io::{BufRead, BufReader, Read},
     process::{Command, Stdio},
-    rc::Rc,
+    sync::Mutex,
     thread::sleep,
     time::{Duration, Instant},
 };
 
+const CRATES_IO_SPARSE_INDEX: &str = "sparse+https://index.crates.io/";
+
 const CRLF: &str = "\r\n";
 const LF: &str = "\n";
 
-lazy_static! {
-    static ref NAME: Regex =
-        Regex::new(r#"^(\s*['"]?name['"]?\s*=\s*['"])([0-9A-Za-z-_]+)(['"].*)$"#).expect(INTERNAL_ERR);
-    static ref VERSION: Regex =
-        Regex::new(r#"^(\s*['"]?version['"]?\s*=\s*['"])([^'"]+)(['"].*)$"#)
-            .expect(INTERNAL_ERR);
-    static ref PACKAGE: Regex =
-        Regex::new(r#"^(\s*['"]?package['"]?\s*=\s*['"])([0-9A-Za-z-_]+)(['"].*)$"#).expect(INTERNAL_ERR);
-    static ref PACKAGE_TABLE: Regex =
-        Regex::new(r#"^\[package]"#).expect(INTERNAL_ERR);
-    static ref PACKAGE_TABLE_WORKSPACE: Regex =
-        Regex::new(r#"^\[workspace\.package]"#).expect(INTERNAL_ERR);
-    static ref DEP_TABLE: Regex =
-        Regex::new(r#"^\[(target\.'?([^']+)'?\.)?dependencies]"#).expect(INTERNAL_ERR);
-    static ref DEP_TABLE_WORKSPACE: Regex =
-        Regex::new(r#"^\[workspace\.dependencies]"#).expect(INTERNAL_ERR);
-    static ref DEP_ENTRY: Regex =
-        Regex::new(r#"^\[dependencies\.([0-9A-Za-z-_]+)]"#).expect(INTERNAL_ERR);
-    static ref DEP_ENTRY_WORKSPACE: Regex =
-        Regex::new(r#"^\[workspace\.dependencies\.([0-9A-Za-z-_]+)]"#).expect(INTERNAL_ERR);
-    static ref BUILD_DEP_TABLE: Regex =
-        Regex::new(r#"^\[(target\.'?([^']+)'?\.)?build-dependencies]"#).expect(INTERNAL_ERR);
-    static ref BUILD_DEP_ENTRY: Regex =
-        Regex::new(r#"^\[build-dependencies\.([0-9A-Za-z-_]+)]"#).expect(INTERNAL_ERR);
-    static ref DEV_DEP_TABLE: Regex =
-        Regex::new(r#"^\[(target\.'?([^']+)'?\.)?dev-dependencies]"#).expect(INTERNAL_ERR);
-    static ref DEV_DEP_ENTRY: Regex =
-        Regex::new(r#"^\[dev-dependencies\.([0-9A-Za-z-_]+)]"#).expect(INTERNAL_ERR);
-    static ref DEP_DIRECT_VERSION: Regex =
-        Regex::new(r#"^(\s*['"]?([0-9A-Za-z-_]+)['"]?\s*=\s*['"])([^'"]+)(['"].*)$"#)
-            .expect(INTERNAL_ERR);
-    static ref DEP_DIRECT_INHERITED: Regex =
-        Regex::new(r#"^\s*['"]?([0-9A-Za-z-_]+)['"]?\s*\.\s*['"]?workspace['"]?\s*=\s*true\s*.*$"#)
-            .expect(INTERNAL_ERR);
-    static ref DEP_OBJ_VERSION: Regex =
-        Regex::new(r#"^(\s*['"]?([0-9A-Za-z-_]+)['"]?\s*=\s*\{.*['"]?version['"]?\s*=\s*['"])([^'"]+)(['"].*}.*)$"#)
-            .expect(INTERNAL_ERR);
-    static ref DEP_OBJ_INHERITED: Regex =
-        Regex::new(r#"^\s*['"]?([0-9A-Za-z-_]+)['"]?\s*=\s*\{.*['"]?workspace['"]?\s*=\s*true\s*.*}.*$"#)
-            .expect(INTERNAL_ERR);
-    static ref DEP_OBJ_RENAME_VERSION: Regex =
-        Regex::new(r#"^(\s*['"]?([0-9A-Za-z-_]+)['"]?\s*=\s*\{.*['"]?version['"]?\s*=\s*['"])([^'"]+)(['"].*['"]?package['"]?\s*=\s*['"]([0-9A-Za-z-_]+)['"].*}.*)$"#)
-            .expect(INTERNAL_ERR);
-    static ref DEP_OBJ_RENAME_BEFORE_VERSION: Regex =
-        Regex::new(r#"^(\s*['"]?[0-9A-Za-z-_]+['"]?\s*=\s*\{.*['"]?package['"]?\s*=\s*['"]([0-9A-Za-z-_]+)['"].*['"]?version['"]?\s*=\s*['"])([^'"]+)(['"].*}.*)$"#)
-            .expect(INTERNAL_ERR);
-    static ref DEP_DIRECT_NAME: Regex =
-        Regex::new(r#"^(\s*['"]?([0-9A-Za-z-_]+)['"]?\s*=\s*)(['"][^'"]+['"])(.*)$"#)
-            .expect(INTERNAL_ERR);
-    static ref DEP_OBJ_NAME: Regex =
-        Regex::new(r#"^(\s*['"]?([0-9A-Za-z-_]+)['"]?\s*=\s*\{(.*[^\s])?)(\s*}.*)$"#)
-            .expect(INTERNAL_ERR);
-    static ref DEP_OBJ_RENAME_NAME: Regex =
-        Regex::new(r#"^(\s*['"]?[0-9A-Za-z-_]+['"]?\s*=\s*\{.*['"]?package['"]?\s*=\s*['"])([0-9A-Za-z-_]+)(['"].*}.*)$"#)
-            .expect(INTERNAL_ERR);
-    static ref WORKSPACE_KEY: Regex =
-        Regex::new(r#"['"]?workspace['"]?\s*=\s*true"#).expect(INTERNAL_ERR);
+pub fn cargo<'a>(
+    root: &Utf8Path,
+    args: &[&'a str],
+    env: &[(&'a str, &'a str)],
+) -> Result<(String, String)> {
+    run_cargo(root, args, env, true)
 }
 
-pub fn cargo<'a>(
+/// Like `cargo` but without echoing the stderr of the command
+pub fn cargo_quiet<'a>(
+    root: &Utf8Path,
+    args: &[&'a str],
+    env: &[(&'a str, &'a str)],
+) -> Result<(String, String)> {
+    run_cargo(root, args, env, false)
+}
+
+fn run_cargo<'a>(
     root: &Utf8Path,
     args: &[&'a str],
     env: &[(&'a str, &'a str)],
+    echo: bool,
 ) -> Result<(String, String)> {
     debug!("cargo", args.join(" "));
 
+    if matches!(args, ["new" | "publish" | "update", ..]) && would_run("cargo", args)? {
+        return Ok((String::new(), String::new()));
+    }
+
     let mut args = args.to_vec();
 
     if TERM_ERR.features().colors_supported() {
@@ -119,7 +82,9 @@ pub fn cargo<'a>(
         for line in BufReader::new(stderr).lines() {
             let line = line?;
 
-            eprintln!("{}", line);
+            if echo {
+                eprintln!("{}", line);
+            }
             stderr_lines.push(line);
         }
     }
@@ -174,7 +139,7 @@ pub fn cargo_config_get(root: &Utf8Path, name: &str) -> Result<String> {
     let args = vec!["-Z", "unstable-options", "config", "get", name];
     let env = &[("RUSTC_BOOTSTRAP", "1")];
 
-    let (stdout, _) = cargo(root, &args, env)?;
+    let (stdout, stderr) = cargo_quiet(root, &args, env)?;
 
     // `cargo config get` returns TOML output, like so:
     //
@@ -191,8 +156,14 @@ pub fn cargo_config_get(root: &Utf8Path, name: &str) -> Result<String> {
         .map(|x| x.to_string())
         .collect::<Vec<_>>();
 
-    // value is "\"some-url\""
-    let value = tokens.get(1).ok_or(Error::BadConfigGetOutput(stdout))?;
+    // value is "\"some-url\"", cargo explains on stderr when there is no value
+    let value = tokens.get(1).ok_or_else(|| {
+        Error::BadConfigGetOutput(if stdout.is_empty() {
+            stderr
+        } else {
+            stdout.clone()
+        })
+    })?;
 
     // we return "some-url"
     Ok(value
@@ -202,146 +173,188 @@ pub fn cargo_config_get(root: &Utf8Path, name: &str) -> Result<String> {
         .into())
 }
 
-#[derive(Debug)]
-enum Context {
-    Beginning,
+/// Files that `cargo package` would include in the crate, relative to its directory
+pub fn package_files(root: &Utf8Path, name: &str, manifest_path: &Utf8Path) -> Result<Vec<String>> {
+    let args = [
+        "package",
+        "--list",
+        "--allow-dirty",
+        "--manifest-path",
+        manifest_path.as_str(),
+    ];
+
+    let (stdout, stderr) = cargo_quiet(root, &args, &[])?;
+
+    if stderr.contains("error:") {
+        TERM_ERR.write_line(&stderr)?;
+        return Err(Error::Package(name.to_string()));
+    }
+
+    Ok(stdout.lines().map(|x| x.trim().to_string()).collect())
+}
+
+#[derive(Copy, Clone)]
+pub enum ManifestDiscriminant {
+    Workspace,
     Package,
-    Dependencies,
-    DependencyEntry(String, Option<(usize, String)>, bool),
-    DontCare,
+    Any,
 }
 
-fn edit_version(
-    caps: Captures,
-    new_lines: &mut Vec<String>,
-    versions: &Map<String, Version>,
-    exact: bool,
-    version_index: usize,
-) -> Result<()> {
-    if let Some(new_version) = versions.get(&caps[version_index]) {
-        if exact {
-            new_lines.push(format!("{}={}{}", &caps[1], new_version, &caps[4]));
-        } else if !VersionReq::parse(&caps[3])?.matches(new_version) {
-            new_lines.push(format!("{}{}{}", &caps[1], new_version, &caps[4]));
-        }
-    }
+use ManifestDiscriminant::*;
 
-    Ok(())
+const DEP_TABLES: [&str; 3] = ["dependencies", "build-dependencies", "dev-dependencies"];
+
+fn get_table_like<'a>(table: &'a mut dyn TableLike, key: &str) -> Option<&'a mut dyn TableLike> {
+    table.get_mut(key).and_then(Item::as_table_like_mut)
 }
 
-fn rename_dep(
-    caps: Captures,
-    new_lines: &mut Vec<String>,
-    renames: &Map<String, String>,
-    name_index: usize,
-) -> Result<()> {
-    if let Some(new_name) = renames.get(&caps[name_index]) {
-        new_lines.push(format!("{}{}{}", &caps[1], new_name, &caps[3]));
+fn is_inherited(dep: &dyn TableLike) -> bool {
+    dep.get("workspace")
+        .and_then(Item::as_bool)
+        .unwrap_or(false)
+}
+
+/// Replaces the string held by `item` while keeping its decor (whitespace and
+/// comments) and its quoting style intact.
+fn set_string(item: &mut Item, new_value: &str) -> Result {
+    if let Some(Value::String(old)) = item.as_value_mut() {
+        let literal = old
+            .as_repr()
+            .and_then(|repr| repr.as_raw().as_str())
+            .is_some_and(|raw| raw.starts_with('\''));
+
+        let mut value = if literal && !new_value.contains('\'') {
+            format!("'{}'", new_value).parse::<Value>()?
+        } else {
+            Value::from(new_value)
+        };
+
+        *value.decor_mut() = old.decor().clone();
+        *old = match value {
+            Value::String(value) => value,
+            _ => unreachable!("{}", INTERNAL_ERR),
+        };
     }
 
     Ok(())
 }
 
-#[derive(Copy, Clone)]
-pub enum ManifestDiscriminant {
-    Workspace,
-    Package,
-    Any,
+/// Appends a new key to a dependency entry. For inline tables, the trailing
+/// whitespace before the closing brace is moved after the new key.
+fn append_string(dep: &mut Item, key: &str, new_value: &str) {
+    match dep {
+        Item::Value(Value::InlineTable(table)) => {
+            let suffix = table
+                .iter_mut()
+                .last()
+                .and_then(|(_, last)| {
+                    let suffix = last.decor().suffix().cloned();
+                    last.decor_mut().set_suffix("");
+                    suffix
+                })
+                .unwrap_or_else(|| " ".into());
+
+            table.insert(key, Value::from(new_value).decorated(" ", suffix));
+        }
+        _ => {
+            if let Some(dep) = dep.as_table_like_mut() {
+                dep.insert(key, value(new_value));
+            }
+        }
+    }
 }
 
-use ManifestDiscriminant::*;
+fn version_value(version: &Version, exact: bool) -> String {
+    if exact {
+        format!("={}", version)
+    } else {
+        version.to_string()
+    }
+}
+
+fn edit_version(item: &mut Item, new_version: &Version, exact: bool) -> Result {
+    if let Some(req) = item.as_str() {
+        if exact || !VersionReq::parse(req)?.matches(new_version) {
+            set_string(item, &version_value(new_version, exact))?;
+        }
+    }
+
+    Ok(())
+}
 
-fn parse<P, D, DE, DP>(
+fn edit<P, D>(
     manifest: String,
     dev_deps: bool,
     md: ManifestDiscriminant,
-    package_f: P,
-    mut dependencies_f: D,
-    dependency_entries_f: DE,
-    mut dependency_pkg_f: DP,
+    mut package_f: P,
+    mut dependency_f: D,
 ) -> Result<String>
 where
-    P: Fn(&str, &mut Vec<String>) -> Result,
-    D: FnMut(&str, &mut Vec<String>) -> Result,
-    DE: Fn(&str, &mut Option<String>) -> (bool, Option<String>),
-    DP: FnMut(&str, Option<(usize, String)>, &mut Vec<String>, bool) -> Result,
+    P: FnMut(&mut dyn TableLike) -> Result,
+    D: FnMut(&str, &mut Item) -> Result,
 {
-    let mut context = Context::Beginning;
-    let mut new_lines = vec![];
-
-    for line in manifest.lines() {
-        let trimmed = line.trim();
-        if trimmed.starts_with('[') {
-            if let Context::DependencyEntry(ref dep, ref mut dep_meta, inherits) = context {
-                dependency_pkg_f(dep, dep_meta.take(), &mut new_lines, inherits)?;
-            }
-        }
-        let count = new_lines.len();
-
-        #[allow(clippy::if_same_then_else)]
-        if let (Any | Package, Some(_)) = (md, PACKAGE_TABLE.captures(trimmed)) {
-            context = Context::Package;
-        } else if let (Workspace, Some(_)) = (md, PACKAGE_TABLE_WORKSPACE.captures(trimmed)) {
-            context = Context::Package;
-        } else if let (Any | Package, Some(_)) = (md, DEP_TABLE.captures(trimmed)) {
-            context = Context::Dependencies;
-        } else if let (Any | Workspace, Some(_)) = (md, DEP_TABLE_WORKSPACE.captures(trimmed)) {
-            context = Context::Dependencies;
-        } else if let Some(_) = BUILD_DEP_TABLE.captures(trimmed) {
-            context = Context::Dependencies;
-        } else if let Some(_) = DEV_DEP_TABLE.captures(trimmed) {
-            // TODO: let-chain
-            if dev_deps {
-                context = Context::Dependencies;
-            } else {
-                context = Context::DontCare;
-            }
-        } else if let (Any | Package, Some(caps)) = (md, DEP_ENTRY.captures(trimmed)) {
-            context = Context::DependencyEntry(caps[1].to_string(), None, false);
-        } else if let (Any | Workspace, Some(caps)) = (md, DEP_ENTRY_WORKSPACE.captures(trimmed)) {
-            context = Context::DependencyEntry(caps[1].to_string(), None, false);
-        } else if let Some(caps) = BUILD_DEP_ENTRY.captures(trimmed) {
-            context = Context::DependencyEntry(caps[1].to_string(), None, false);
-        } else if let Some(caps) = DEV_DEP_ENTRY.captures(trimmed) {
-            // TODO: let-chain
-            if dev_deps {
-                context = Context::DependencyEntry(caps[1].to_string(), None, false);
-            } else {
-                context = Context::DontCare;
+    let mut document = manifest.parse::<Document>()?;
+    let root = document.as_table_mut() as &mut dyn TableLike;
+
+    let package = match md {
+        Any | Package => get_table_like(root, "package"),
+        Workspace => get_table_like(root, "workspace").and_then(|x| get_table_like(x, "package")),
+    };
+
+    if let Some(package) = package {
+        package_f(package)?;
+    }
+
+    let dep_tables = if dev_deps {
+        &DEP_TABLES[..]
+    } else {
+        &DEP_TABLES[..2]
+    };
+
+    let mut tables = vec![];
+
+    for (key, item) in root.iter_mut() {
+        let table = match item.as_table_like_mut() {
+            Some(table) => table,
+            None => continue,
+        };
+
+        match (md, key.get()) {
+            (Any | Workspace, "workspace") => {
+                tables.extend(get_table_like(table, "dependencies"));
             }
-        } else if trimmed.starts_with('[') {
-            context = Context::DontCare;
-        } else {
-            // TODO: Support `package.version` like stuff (with quotes) at beginning
-            match &mut context {
-                Context::Package => package_f(line, &mut new_lines)?,
-                Context::Dependencies => dependencies_f(line, &mut new_lines)?,
-                Context::DependencyEntry(dep, dep_meta, inherits) => {
-                    let mut line_meta = None;
-
-                    let (_inherits, new_dep) = dependency_entries_f(line, &mut line_meta);
-                    *inherits |= _inherits;
-                    if let Some(new_dep) = new_dep {
-                        *dep = new_dep;
-                    }
-                    if let Some(meta) = line_meta {
-                        dep_meta.replace((new_lines.len(), meta));
+            (Any | Package, "target") => {
+                for (_, platform) in table.iter_mut() {
+                    if let Some(platform) = platform.as_table_like_mut() {
+                        for (key, item) in platform.iter_mut() {
+                            if dep_tables.contains(&key.get()) {
+                                tables.extend(item.as_table_like_mut());
+                            }
+                        }
                     }
                 }
-                _ => {}
             }
+            (Any | Package, key) if dep_tables.contains(&key) => tables.push(table),
+            _ => {}
         }
+    }
 
-        if new_lines.len() == count {
-            new_lines.push(line.to_string());
+    for table in tables {
+        for (key, item) in table.iter_mut() {
+            dependency_f(key.get(), item)?;
         }
     }
 
-    if let Context::DependencyEntry(ref dep, dep_meta, inherits) = context {
-        dependency_pkg_f(dep, dep_meta, &mut new_lines, inherits)?;
+    let mut edited = document.to_string();
+
+    if manifest.contains(CRLF) {
+        edited = edited.replace(CRLF, LF).replace(LF, CRLF);
     }
 
-    Ok(new_lines.join(if manifest.contains(CRLF) { CRLF } else { LF }))
+    // Make sure we never write out a manifest we can't read back
+    edited.parse::<Document>()?;
+
+    Ok(edited)
 }
 
 pub fn rename_packages(
@@ -349,63 +362,48 @@ pub fn rename_packages(
     pkg_name: &str,
     renames: &Map<String, String>,
 ) -> Result<String> {
-    parse(
+    edit(
         manifest,
         true,
         Any,
-        |line, new_lines| {
-            if let Some(to) = renames.get(pkg_name) {
-                if let Some(caps) = NAME.captures(line) {
-                    new_lines.push(format!("{}{}{}", &caps[1], to, &caps[3]));
-                }
+        |package| {
+            if let (Some(to), Some(name)) = (renames.get(pkg_name), package.get_mut("name")) {
+                set_string(name, to)?;
             }
 
             Ok(())
         },
-        |line, new_lines| {
-            if let Some(caps) = DEP_DIRECT_NAME.captures(line) {
-                if let Some(new_name) = renames.get(&caps[2]) {
-                    new_lines.push(format!(
-                        "{}{{ version = {}, package = \"{}\" }}{}",
-                        &caps[1], &caps[3], new_name, &caps[4]
-                    ));
+        |dep_name, dep| {
+            if let Some(version) = dep.as_value().filter(|x| x.is_str()) {
+                if let Some(new_name) = renames.get(dep_name) {
+                    let mut version = version.clone();
+                    let decor = version.decor().clone();
+                    version.decor_mut().clear();
+
+                    let mut table = InlineTable::new();
+                    table.insert("version", version);
+                    table.insert("package", new_name.into());
+
+                    let mut table = Value::InlineTable(table);
+                    *table.decor_mut() = decor;
+
+                    *dep = Item::Value(table);
                 }
-            } else if let Some(caps) = DEP_OBJ_RENAME_NAME.captures(line) {
-                rename_dep(caps, new_lines, renames, 2)?;
-            } else if let Some(caps) = DEP_OBJ_NAME.captures(line) {
-                if let Some(new_name) = renames.get(&caps[2]) {
-                    if WORKSPACE_KEY.captures(&caps[3]).is_none() {
-                        new_lines.push(format!(
-                            "{}, package = \"{}\"{}",
-                            &caps[1], new_name, &caps[4]
-                        ));
-                    }
+            } else if let Some(table) = dep.as_table_like_mut() {
+                if is_inherited(table) {
+                    return Ok(());
                 }
-            }
 
-            Ok(())
-        },
-        |line, package_line| {
-            if PACKAGE.is_match(line) {
-                package_line.replace(line.to_string());
-            }
-
-            (false, None)
-        },
-        |dep, package_line, new_lines, _| {
-            match package_line {
-                Some((i, line)) => {
-                    if let (Some(line), Some(caps)) =
-                        (new_lines.get_mut(i), PACKAGE.captures(&line))
-                    {
-                        if let Some(new_name) = renames.get(&caps[2]) {
-                            *line = format!("{}{}{}", &caps[1], new_name, &caps[3]);
+                match table.get_mut("package") {
+                    Some(package) => {
+                        if let Some(new_name) = package.as_str().and_then(|x| renames.get(x)) {
+                            set_string(package, new_name)?;
                         }
                     }
-                }
-                None => {
-                    if let Some(new_name) = renames.get(dep) {
-                        new_lines.push(format!("package = \"{}\"", new_name));
+                    None => {
+                        if let Some(new_name) = renames.get(dep_name) {
+                            append_string(dep, "package", new_name);
+                        }
                     }
                 }
             }
@@ -423,81 +421,42 @@ pub fn change_versions(
     exact: bool,
     inherited: &mut HashSet<String>,
 ) -> Result<String> {
-    let inherited = Rc::new(RefCell::new(inherited));
-    parse(
+    edit(
         manifest,
         false,
         dis,
-        |line, new_lines| {
-            if let Some(new_version) = versions.get(pkg_name) {
-                if let Some(caps) = VERSION.captures(line) {
-                    new_lines.push(format!("{}{}{}", &caps[1], new_version, &caps[3]));
-                }
+        |package| {
+            if let (Some(new_version), Some(version)) =
+                (versions.get(pkg_name), package.get_mut("version"))
+            {
+                set_string(version, &new_version.to_string())?;
             }
 
             Ok(())
         },
-        |line, new_lines| {
-            if let Some(caps) = DEP_DIRECT_INHERITED.captures(line) {
-                inherited.borrow_mut().insert(caps[1].to_string());
-            } else if let Some(caps) = DEP_OBJ_INHERITED.captures(line) {
-                inherited.borrow_mut().insert(caps[1].to_string());
-            } else if let Some(caps) = DEP_DIRECT_VERSION.captures(line) {
-                edit_version(caps, new_lines, versions, exact, 2)?;
-            } else if let Some(caps) = DEP_OBJ_RENAME_VERSION.captures(line) {
-                edit_version(caps, new_lines, versions, exact, 5)?;
-            } else if let Some(caps) = DEP_OBJ_RENAME_BEFORE_VERSION.captures(line) {
-                edit_version(caps, new_lines, versions, exact, 2)?;
-            } else if let Some(caps) = DEP_OBJ_VERSION.captures(line) {
-                edit_version(caps, new_lines, versions, exact, 2)?;
-            } else if let Some(caps) = DEP_OBJ_NAME.captures(line) {
-                if let Some(new_version) = versions.get(&caps[2]) {
-                    if exact {
-                        new_lines.push(format!(
-                            "{}, version = \"={}\"{}",
-                            &caps[1], new_version, &caps[4]
-                        ));
-                    } else {
-                        new_lines.push(format!(
-                            "{}, version = \"{}\"{}",
-                            &caps[1], new_version, &caps[4]
-                        ));
-                    }
+        |dep_name, dep| {
+            if dep.is_str() {
+                if let Some(new_version) = versions.get(dep_name) {
+                    edit_version(dep, new_version, exact)?;
+                }
+            } else if let Some(table) = dep.as_table_like_mut() {
+                if is_inherited(table) {
+                    inherited.insert(dep_name.to_string());
+                    return Ok(());
                 }
-            }
 
-            Ok(())
-        },
-        |line, version_line| {
-            if let Some(_) = WORKSPACE_KEY.captures(line) {
-                return (true, None);
-            } else if let Some(caps) = PACKAGE.captures(line) {
-                return (false, Some(caps[2].to_string()));
-            } else if VERSION.is_match(line) {
-                version_line.replace(line.to_string());
-            }
+                let name = table
+                    .get("package")
+                    .and_then(Item::as_str)
+                    .unwrap_or(dep_name)
+                    .to_string();
 
-            (false, None)
-        },
-        |dep, version_line, new_lines, inherits| {
-            if inherits {
-                inherited.borrow_mut().insert(dep.to_string());
-            } else if let Some((i, line)) = version_line {
-                if let (Some(line), Some(caps), Some(new_version)) = (
-                    new_lines.get_mut(i),
-                    VERSION.captures(&line),
-                    versions.get(dep),
-                ) {
-                    if exact {
-                        *line = format!("{}={}{}", &caps[1], new_version, &caps[3]);
-                    } else if !VersionReq::parse(&caps[2])?.matches(new_version) {
-                        *line = format!("{}{}{}", &caps[1], new_version, &caps[3]);
+                if let Some(new_version) = versions.get(&name) {
+                    match table.get_mut("version") {
+                        Some(version) => edit_version(version, new_version, exact)?,
+                        None => append_string(dep, "version", &version_value(new_version, exact)),
                     }
                 }
-            } else {
-                if let Some(new_version) = versions.get(dep) {
-                    new_lines.push(format!("version = \"{}\"", new_version));
-                }
             }
 
             Ok(())
@@ -534,29 +493,83 @@ pub fn is_unversioned(v: &impl VersionSpec) -> bool {
     VersionSpec::is_unversioned(v)
 }
 
-pub fn is_published(index: &mut Index, name: &str, version: &str) -> Result<bool> {
-    // See if we already have the crate (and version) in cache
-    if let Some(crate_data) = index.crate_(name) {
-        if crate_data.versions().iter().any(|v| v.version() == version) {
-            return Ok(true);
+/// Index of a registry, either a git repository or served over the sparse HTTP protocol
+pub enum RegistryIndex {
+    Git(Index),
+    /// Base URL of the index files, without the `sparse+` prefix
+    Sparse(String),
+}
+
+impl RegistryIndex {
+    /// Opens the index at a URL as it is written in the cargo config
+    pub fn from_url(url: &str) -> Result<Self> {
+        if let Some(url) = url.strip_prefix("sparse+") {
+            return Ok(Self::Sparse(url.trim_end_matches('/').to_string()));
         }
+
+        Ok(Self::Git(Index::from_url(&format!("registry+{}", url))?))
     }
 
-    // We don't? Okay, update the cache then
-    index.update()?;
+    /// Opens the crates.io index, which is sparse unless cargo is configured to use git
+    pub fn crates_io(root: &Utf8Path) -> Result<Self> {
+        match cargo_config_get(root, "registries.crates-io.protocol") {
+            Ok(protocol) if protocol == "git" => Ok(Self::Git(Index::new_cargo_default()?)),
+            _ => Self::from_url(CRATES_IO_SPARSE_INDEX),
+        }
+    }
 
-    // Try again with updated index:
-    if let Some(crate_data) = index.crate_(name) {
-        if crate_data.versions().iter().any(|v| v.version() == version) {
-            return Ok(true);
+    /// Fetches the latest state of a git index, sparse indexes are always fetched fresh
+    pub fn update(&mut self) -> Result {
+        if let Self::Git(index) = self {
+            index.update()?;
         }
+
+        Ok(())
+    }
+
+    fn has_version(&self, name: &str, version: &str) -> Result<bool> {
+        let crate_data = match self {
+            Self::Git(index) => index.crate_(name),
+            Self::Sparse(url) => {
+                let url = format!("{}/{}", url, index_path(name));
+
+                match ureq::get(&url).call() {
+                    Ok(response) => {
+                        let mut bytes = vec![];
+                        response.into_reader().read_to_end(&mut bytes)?;
+                        Some(Crate::from_slice(&bytes)?)
+                    }
+                    // Cargo treats these as the crate not existing in the registry
+                    Err(ureq::Error::Status(404 | 410 | 451, _)) => None,
+                    Err(err) => return Err(Error::SparseRegistry(url, err.to_string())),
+                }
+            }
+        };
+
+        Ok(crate_data
+            .is_some_and(|crate_data| crate_data.versions().iter().any(|v| v.version() == version)))
     }
+}
+
+/// Path of the file describing a crate in the index
+pub fn index_path(name: &str) -> String {
+    let name = name.to_lowercase();
+
+    match name.len() {
+        1 => format!("1/{}", name),
+        2 => format!("2/{}", name),
+        3 => format!("3/{}/{}", &name[..1], name),
+        _ => format!("{}/{}/{}", &name[..2], &name[2..4], name),
+    }
+}
 
-    // I guess we didn't have it
-    Ok(false)
+/// Looks up a crate version in the index as it was last fetched
+pub fn is_published(index: &Mutex<RegistryIndex>, name: &str, version: &str) -> Result<bool> {
+    index.lock().expect(INTERNAL_ERR).has_version(name, version)
 }
 
-pub fn check_index(index: &mut Index, name: &str, version: &str) -> Result<()> {
+/// Waits for a crate version to be visible in the index, refreshing it in between
+pub fn check_index(index: &Mutex<RegistryIndex>, name: &str, version: &str) -> Result<()> {
     let now = Instant::now();
     let sleep_time = Duration::from_secs(2);
     let timeout = Duration::from_secs(300);
@@ -570,11 +583,17 @@ pub fn check_index(index: &mut Index, name: &str, version: &str) -> Result<()> {
         }
 
         if !logged {
-            info!("waiting", "...");
+            info!(
+                "waiting",
+                format!("for {} v{} to be available", name, version)
+            );
             logged = true;
         }
 
         sleep(sleep_time);
+
+        // Concurrent crates share the index, so only one of them updates it at a time
+        index.lock().expect(INTERNAL_ERR).update()?;
     }
 
     Ok(())
@@ -607,8 +626,8 @@ mod test {
             .unwrap(),
             indoc! {r#"
                 [package]
-                version = "0.3.0""#
-            }
+                version = "0.3.0"
+            "#}
         );
     }
 
@@ -634,8 +653,8 @@ mod test {
             .unwrap(),
             indoc! {r#"
                 [package]
-                version="0.3.0" # hello"#
-            }
+                version="0.3.0" # hello
+            "#}
         );
     }
 
@@ -661,8 +680,8 @@ mod test {
             .unwrap(),
             indoc! {r#"
                 [package]
-                "version"	=	"0.3.0""#
-            }
+                "version"	=	"0.3.0"
+            "#}
         );
     }
 
@@ -688,8 +707,8 @@ mod test {
             .unwrap(),
             indoc! {r#"
                 [package]
-                'version'='0.3.0'# hello"#
-            }
+                'version'='0.3.0'# hello
+            "#}
         );
     }
 
@@ -715,8 +734,8 @@ mod test {
             .unwrap(),
             indoc! {r#"
                 [workspace.package]
-                version = "0.3.0" # hello"#
-            }
+                version = "0.3.0" # hello
+            "#}
         );
     }
 
@@ -742,8 +761,8 @@ mod test {
             .unwrap(),
             indoc! {r#"
                 [dependencies]
-                this = "0.3.0" # hello"#
-            }
+                this = "0.3.0" # hello
+            "#}
         );
     }
 
@@ -769,8 +788,8 @@ mod test {
             .unwrap(),
             indoc! {r#"
                 [dependencies]
-                this = { path = "../", version = "0.3.0" } # hello"#
-            }
+                this = { path = "../", version = "0.3.0" } # hello
+            "#}
         );
     }
 
@@ -782,7 +801,7 @@ mod test {
         "#};
 
         let mut v = Map::new();
-        v.insert("this".to_string(), Version::parse("0.3.0").unwrap());
+        v.insert("ra_this".to_string(), Version::parse("0.3.0").unwrap());
 
         assert_eq!(
             change_versions(
@@ -796,8 +815,8 @@ mod test {
             .unwrap(),
             indoc! {r#"
                 [dependencies]
-                this = { path = "../", package = "ra_this", version = "0.3.0" } # hello"#
-            }
+                this = { path = "../", package = "ra_this", version = "0.3.0" } # hello
+            "#}
         );
     }
 
@@ -823,13 +842,12 @@ mod test {
             .unwrap(),
             indoc! {r#"
                 [dependencies]
-                this = { path = "../", version = "0.3.0" } # hello"#
-            }
+                this = { path = "../", version = "0.3.0" } # hello
+            "#}
         );
     }
 
     #[test]
-    #[ignore = "multi-line object entries are not supported yet"]
     fn test_version_dependencies_object_multiline() {
         let m = indoc! {r#"
             [dependencies]
@@ -857,8 +875,8 @@ mod test {
                 this = { path = "../", features = [
                     "foo",
                     "bar"
-                ], version = "0.3.0" } # hello"#
-            }
+                ], version = "0.3.0" } # hello
+            "#}
         );
     }
 
@@ -884,8 +902,8 @@ mod test {
             .unwrap(),
             indoc! {r#"
                 [dependencies]
-                this2 = { path = "../", version = "0.3.0", package = "this" } # hello"#
-            }
+                this2 = { path = "../", version = "0.3.0", package = "this" } # hello
+            "#}
         );
     }
 
@@ -911,8 +929,8 @@ mod test {
             .unwrap(),
             indoc! {r#"
                 [dependencies]
-                this2 = { path = "../", package = "this", version = "0.3.0" } # hello"#
-            }
+                this2 = { path = "../", package = "this", version = "0.3.0" } # hello
+            "#}
         );
     }
 
@@ -940,8 +958,8 @@ mod test {
             indoc! {r#"
                 [dependencies.this]
                 path = "../"
-                version = "0.3.0" # hello"#
-            }
+                version = "0.3.0" # hello
+            "#}
         );
     }
 
@@ -995,8 +1013,8 @@ mod test {
 
                 [dev-dependencies.dev-other]
                 path = "../"
-                workspace = true"#
-            }
+                workspace = true
+            "#}
         );
 
         assert_eq!(inherited.len(), 2);
@@ -1031,7 +1049,8 @@ mod test {
                 path = "../" # hello
                 version = "0.3.0"
                 [package]
-                name = "test""#}
+                name = "test"
+            "#}
         );
     }
 
@@ -1061,8 +1080,8 @@ mod test {
                 [dependencies.this2]
                 path = "../"
                 version = "0.3.0" # hello"
-                package = "this""#
-            }
+                package = "this"
+            "#}
         );
     }
 
@@ -1092,8 +1111,8 @@ mod test {
                 [dependencies.this2]
                 path = "../"
                 package = "this"
-                version = "0.3.0" # hello"#
-            }
+                version = "0.3.0" # hello
+            "#}
         );
     }
 
@@ -1119,8 +1138,8 @@ mod test {
             .unwrap(),
             indoc! {r#"
                 [target.x86_64-pc-windows-gnu.dependencies]
-                this = "0.3.0" # hello"#
-            }
+                this = "0.3.0" # hello
+            "#}
         );
     }
 
@@ -1146,8 +1165,8 @@ mod test {
             .unwrap(),
             indoc! {r#"
                 [target.'cfg(not(any(target_arch = "wasm32", target_os = "emscripten")))'.dependencies]
-                this = "0.3.0" # hello"#
-            }
+                this = "0.3.0" # hello
+            "#}
         );
     }
 
@@ -1185,8 +1204,8 @@ mod test {
 
                 [dev-dependencies]
                 dev-this = { workspace = true } # hello
-                dev-other = { workspace= true } # hello"#
-            }
+                dev-other = { workspace= true } # hello
+            "#}
         );
 
         assert_eq!(inherited.len(), 2);
@@ -1222,8 +1241,8 @@ mod test {
                 this = "0.0.1" # hello
 
                 [workspace.dependencies]
-                this = "0.3.0" # hello"#
-            }
+                this = "0.3.0" # hello
+            "#}
         );
 
         assert_eq!(
@@ -1241,8 +1260,8 @@ mod test {
                 this = "0.3.0" # hello
 
                 [workspace.dependencies]
-                this = "0.0.1" # hello"#
-            }
+                this = "0.0.1" # hello
+            "#}
         );
     }
 
@@ -1280,8 +1299,8 @@ mod test {
 
                 [dev-dependencies]
                 dev-this.workspace = true # hello
-                dev-other.workspace=true# hello"#
-            }
+                dev-other.workspace=true# hello
+            "#}
         );
 
         assert_eq!(inherited.len(), 2);
@@ -1311,8 +1330,8 @@ mod test {
             .unwrap(),
             indoc! {r#"
                 [dependencies]
-                this = { path = "../", version = "=0.3.0" } # hello"#
-            }
+                this = { path = "../", version = "=0.3.0" } # hello
+            "#}
         );
     }
 
@@ -1338,8 +1357,8 @@ mod test {
             .unwrap(),
             indoc! {r#"
                 [dependencies]
-                this = { path = "../", version = "=0.3.0" } # hello"#
-            }
+                this = { path = "../", version = "=0.3.0" } # hello
+            "#}
         );
     }
 
@@ -1357,8 +1376,8 @@ mod test {
             rename_packages(m.into(), "this", &v).unwrap(),
             indoc! {r#"
                 [package]
-                name = "ra_this""#
-            }
+                name = "ra_this"
+            "#}
         );
     }
 
@@ -1376,8 +1395,8 @@ mod test {
             rename_packages(m.into(), "another", &v).unwrap(),
             indoc! {r#"
                 [dependencies]
-                this = { version = "0.0.1", package = "ra_this" } # hello"#
-            }
+                this = { version = "0.0.1", package = "ra_this" } # hello
+            "#}
         );
     }
 
@@ -1395,8 +1414,8 @@ mod test {
             rename_packages(m.into(), "another", &v).unwrap(),
             indoc! {r#"
                 [dependencies]
-                this = { path = "../", version = "0.0.1", package = "ra_this" } # hello"#
-            }
+                this = { path = "../", version = "0.0.1", package = "ra_this" } # hello
+            "#}
         );
     }
 
@@ -1414,8 +1433,8 @@ mod test {
             rename_packages(m.into(), "another", &v).unwrap(),
             indoc! {r#"
                 [dependencies]
-                this2 = { path = "../", version = "0.0.1", package = "ra_this" } # hello"#
-            }
+                this2 = { path = "../", version = "0.0.1", package = "ra_this" } # hello
+            "#}
         );
     }
 
@@ -1433,8 +1452,8 @@ mod test {
             rename_packages(m.into(), "another", &v).unwrap(),
             indoc! {r#"
                 [dependencies]
-                this2 = { path = "../", package = "ra_this", version = "0.0.1" } # hello"#
-            }
+                this2 = { path = "../", package = "ra_this", version = "0.0.1" } # hello
+            "#}
         );
     }
 
@@ -1455,8 +1474,8 @@ mod test {
                 [dependencies.this]
                 path = "../"
                 version = "0.0.1" # hello
-                package = "ra_this""#
-            }
+                package = "ra_this"
+            "#}
         );
     }
 
@@ -1478,8 +1497,8 @@ mod test {
                 [dependencies.this2]
                 path = "../"
                 version = "0.0.1" # hello"
-                package = "ra_this""#
-            }
+                package = "ra_this"
+            "#}
         );
     }
 
@@ -1501,8 +1520,8 @@ mod test {
                 [dependencies.this2]
                 path = "../"
                 package = "ra_this"
-                version = "0.0.1" # hello"#
-            }
+                version = "0.0.1" # hello
+            "#}
         );
     }
 
@@ -1520,8 +1539,8 @@ mod test {
             rename_packages(m.into(), "another", &v).unwrap(),
             indoc! {r#"
                 [target.x86_64-pc-windows-gnu.dependencies]
-                this = { version = "0.0.1", package = "ra_this" } # hello"#
-            }
+                this = { version = "0.0.1", package = "ra_this" } # hello
+            "#}
         );
     }
 
@@ -1539,8 +1558,8 @@ mod test {
             rename_packages(m.into(), "another", &v).unwrap(),
             indoc! {r#"
                 [target.'cfg(not(any(target_arch = "wasm32", target_os = "emscripten")))'.dependencies]
-                this = { version = "0.0.1", package = "ra_this" } # hello"#
-            }
+                this = { version = "0.0.1", package = "ra_this" } # hello
+            "#}
         );
     }
 
@@ -1558,8 +1577,8 @@ mod test {
             rename_packages(m.into(), "another", &v).unwrap(),
             indoc! {r#"
                 [dependencies]
-                this = { workspace = true } # hello"#
-            }
+                this = { workspace = true } # hello
+            "#}
         );
     }
 
@@ -1577,8 +1596,8 @@ mod test {
             rename_packages(m.into(), "another", &v).unwrap(),
             indoc! {r#"
                 [dependencies]
-                this = { workspace = true, optional = true } # hello"#
-            }
+                this = { workspace = true, optional = true } # hello
+            "#}
         );
     }
 
@@ -1596,8 +1615,8 @@ mod test {
             rename_packages(m.into(), "another", &v).unwrap(),
             indoc! {r#"
                 [dependencies]
-                this.workspace = true # hello"#
-            }
+                this.workspace = true # hello
+            "#}
         );
     }
 
@@ -1609,9 +1628,6 @@ mod test {
 
             [workspace.package] # cargo doesn't allow name inheritance anyway
             name = "this" # hello
-
-            [package]
-            name = "this" # hello
         "#};
 
         let mut v = Map::new();
@@ -1625,10 +1641,7 @@ mod test {
 
                 [workspace.package] # cargo doesn't allow name inheritance anyway
                 name = "this" # hello
-
-                [package]
-                name = "ra_this" # hello"#
-            }
+            "#}
         );
     }
 
@@ -1646,8 +1659,259 @@ mod test {
             rename_packages(m.into(), "another", &v).unwrap(),
             indoc! {r#"
                 [workspace.dependencies]
-                this = { version = "0.0.1", package = "ra_this" } # hello"#
-            }
+                this = { version = "0.0.1", package = "ra_this" } # hello
+            "#}
+        );
+    }
+
+    #[test]
+    fn test_version_dependencies_dotted() {
+        let m = indoc! {r#"
+            [dependencies]
+            this.path = "../"
+            this.version = "0.0.1" # hello
+        "#};
+
+        let mut v = Map::new();
+        v.insert("this".to_string(), Version::parse("0.3.0").unwrap());
+
+        assert_eq!(
+            change_versions(
+                m.into(),
+                "another",
+                &v,
+                ManifestDiscriminant::Package,
+                false,
+                &mut HashSet::new()
+            )
+            .unwrap(),
+            indoc! {r#"
+                [dependencies]
+                this.path = "../"
+                this.version = "0.3.0" # hello
+            "#}
+        );
+    }
+
+    #[test]
+    fn test_version_dotted_dependencies_table() {
+        let m = indoc! {r#"
+            dependencies.this = { path = "../", version = "0.0.1" } # hello
+
+            [package]
+            version = "0.1.0"
+        "#};
+
+        let mut v = Map::new();
+        v.insert("this".to_string(), Version::parse("0.3.0").unwrap());
+
+        assert_eq!(
+            change_versions(
+                m.into(),
+                "another",
+                &v,
+                ManifestDiscriminant::Package,
+                false,
+                &mut HashSet::new()
+            )
+            .unwrap(),
+            indoc! {r#"
+                dependencies.this = { path = "../", version = "0.3.0" } # hello
+
+                [package]
+                version = "0.1.0"
+            "#}
+        );
+    }
+
+    #[test]
+    fn test_version_comments_with_quotes() {
+        let m = indoc! {r#"
+            [package]
+            # version = "0.0.1"
+            version = "0.1.0" # "quoted" and 'quoted'
+
+            [dependencies]
+            # this = "0.0.1"
+            this = { path = "../", version = "0.0.1" } # version = "0.0.1"
+        "#};
+
+        let mut v = Map::new();
+        v.insert("another".to_string(), Version::parse("0.2.0").unwrap());
+        v.insert("this".to_string(), Version::parse("0.3.0").unwrap());
+
+        assert_eq!(
+            change_versions(
+                m.into(),
+                "another",
+                &v,
+                ManifestDiscriminant::Package,
+                false,
+                &mut HashSet::new()
+            )
+            .unwrap(),
+            indoc! {r#"
+                [package]
+                # version = "0.0.1"
+                version = "0.2.0" # "quoted" and 'quoted'
+
+                [dependencies]
+                # this = "0.0.1"
+                this = { path = "../", version = "0.3.0" } # version = "0.0.1"
+            "#}
+        );
+    }
+
+    #[test]
+    fn test_version_crlf() {
+        let m = "[package]\r\nversion = \"0.1.0\"\r\n\r\n[dependencies.this]\r\npath = \"../\"\r\n";
+
+        let mut v = Map::new();
+        v.insert("another".to_string(), Version::parse("0.2.0").unwrap());
+        v.insert("this".to_string(), Version::parse("0.3.0").unwrap());
+
+        assert_eq!(
+            change_versions(
+                m.into(),
+                "another",
+                &v,
+                ManifestDiscriminant::Package,
+                false,
+                &mut HashSet::new()
+            )
+            .unwrap(),
+            "[package]\r\nversion = \"0.2.0\"\r\n\r\n[dependencies.this]\r\npath = \"../\"\r\nversion = \"0.3.0\"\r\n"
         );
     }
+
+    #[test]
+    fn test_version_invalid_manifest() {
+        let m = indoc! {r#"
+            [package
+            version = "0.1.0"
+        "#};
+
+        assert!(change_versions(
+            m.into(),
+            "this",
+            &Map::new(),
+            ManifestDiscriminant::Package,
+            false,
+            &mut HashSet::new()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_name_dependencies_dotted() {
+        let m = indoc! {r#"
+            [dependencies]
+            this.path = "../"
+            this.version = "0.0.1" # hello
+            other.workspace = true
+        "#};
+
+        let mut v = Map::new();
+        v.insert("this".to_string(), "ra_this".to_string());
+        v.insert("other".to_string(), "ra_other".to_string());
+
+        assert_eq!(
+            rename_packages(m.into(), "another", &v).unwrap(),
+            indoc! {r#"
+                [dependencies]
+                this.path = "../"
+                this.version = "0.0.1" # hello
+                this.package = "ra_this"
+                other.workspace = true
+            "#}
+        );
+    }
+
+    #[test]
+    fn test_name_dev_dependencies_object_multiline() {
+        let m = indoc! {r#"
+            [dev-dependencies]
+            this = { path = "../", features = [
+                "foo",
+            ] } # hello
+        "#};
+
+        let mut v = Map::new();
+        v.insert("this".to_string(), "ra_this".to_string());
+
+        assert_eq!(
+            rename_packages(m.into(), "another", &v).unwrap(),
+            indoc! {r#"
+                [dev-dependencies]
+                this = { path = "../", features = [
+                    "foo",
+                ], package = "ra_this" } # hello
+            "#}
+        );
+    }
+
+    /// Serves the given index files over HTTP, returning the index URL
+    fn serve_index(files: &'static [(&'static str, &'static str)]) -> String {
+        use std::{io::Write, net::TcpListener, thread::spawn};
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("sparse+http://{}/", listener.local_addr().unwrap());
+
+        spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+
+                let request = lines.next().unwrap().unwrap();
+                let path = request.split(' ').nth(1).unwrap().to_string();
+
+                // Skip the headers
+                while !lines.next().unwrap().unwrap().is_empty() {}
+
+                let response = match files.iter().find(|(file, _)| path == format!("/{}", file)) {
+                    Some((_, body)) => format!(
+                        "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    ),
+                    None => {
+                        "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                            .to_string()
+                    }
+                };
+
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+
+        url
+    }
+
+    #[test]
+    fn test_index_path() {
+        assert_eq!(index_path("a"), "1/a");
+        assert_eq!(index_path("ab"), "2/ab");
+        assert_eq!(index_path("abc"), "3/a/abc");
+        assert_eq!(index_path("Serde_JSON"), "se/rd/serde_json");
+    }
+
+    #[test]
+    fn test_sparse_is_published() {
+        let url = serve_index(&[(
+            "de/p1/dep1",
+            concat!(
+                r#"{"name":"dep1","vers":"0.1.0","deps":[],"cksum":"0000000000000000000000000000000000000000000000000000000000000000","features":{},"yanked":false}"#,
+                "\n",
+                r#"{"name":"dep1","vers":"0.2.0","deps":[],"cksum":"0000000000000000000000000000000000000000000000000000000000000000","features":{},"yanked":false}"#,
+                "\n",
+            ),
+        )]);
+
+        let index = Mutex::new(RegistryIndex::from_url(&url).unwrap());
+
+        assert!(is_published(&index, "dep1", "0.1.0").unwrap());
+        assert!(is_published(&index, "dep1", "0.2.0").unwrap());
+        assert!(!is_published(&index, "dep1", "0.3.0").unwrap());
+        assert!(!is_published(&index, "dep2", "0.1.0").unwrap());
+    }
 }
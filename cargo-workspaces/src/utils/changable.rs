@@ -1,27 +1,75 @@
 use crate::utils::{
-    get_group_packages, git, info, Error, GroupName, Pkg, WorkspaceConfig, INTERNAL_ERR,
+    debug, get_group_packages, get_json_messages, git, glob_set, info, list_pkgs, package_files,
+    Error, Event, Graph, GroupName, ListOpt, Listable, Pkg, TagPrefixes, WorkspaceConfig,
+    INTERNAL_ERR,
 };
 use cargo_metadata::Metadata;
 use clap::Parser;
-use globset::{Error as GlobsetError, Glob};
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use oclif::console::style;
 use regex::Regex;
 use semver::Version;
-use std::path::Path;
+use serde::Serialize;
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Component, Path, PathBuf},
+};
+
+/// Packages along with their group name and the group's fixed version
+pub type GroupedPkgs = Vec<((GroupName, Option<Version>), Pkg)>;
+
+/// References the crates are compared with to find their changes
+#[derive(Debug, Default)]
+pub struct Baselines {
+    /// Reference for the crates without one of their own, everything has changed if `None`
+    pub since: Option<String>,
+    /// Last release of the crates, `None` if they were never released
+    tags: HashMap<String, Option<String>>,
+}
+
+impl Baselines {
+    /// Compares all the crates with the given reference
+    pub fn new(since: Option<String>) -> Self {
+        Self {
+            since,
+            tags: HashMap::new(),
+        }
+    }
+
+    /// Reference the crate is compared with, `None` if its whole history counts
+    pub fn of(&self, name: &str) -> Option<&str> {
+        match self.tags.get(name) {
+            Some(tag) => tag.as_deref(),
+            None => self.since.as_deref(),
+        }
+    }
+}
 
 #[derive(Debug, Parser)]
 pub struct ChangeOpt {
-    // TODO: include_dirty
+    /// Include the uncommitted changes of tracked files
+    #[clap(long)]
+    pub include_dirty: bool,
+
+    /// Include the untracked files
+    #[clap(long)]
+    pub include_untracked: bool,
+
     /// Include tags from merged branches
     #[clap(long)]
     pub include_merged_tags: bool,
 
     /// Always include targeted crates matched by glob even when there are no changes
-    #[clap(long, value_name = "pattern")]
-    pub force: Option<String>,
+    #[clap(long, value_name = "pattern", multiple_occurrences = true)]
+    pub force: Vec<String>,
 
     /// Ignore changes in files matched by glob
-    #[clap(long, value_name = "pattern")]
-    pub ignore_changes: Option<String>,
+    #[clap(long, value_name = "pattern", multiple_occurrences = true)]
+    pub ignore_changes: Vec<String>,
+
+    /// Ignore changes in files that are not packaged with the crates
+    #[clap(long)]
+    pub ignore_unpackaged: bool,
 }
 
 #[derive(Debug, Default)]
@@ -34,9 +82,19 @@ pub struct ChangeData {
 }
 
 impl ChangeData {
-    pub fn new(metadata: &Metadata, change: &ChangeOpt) -> Result<Self, Error> {
+    pub fn new(
+        metadata: &Metadata,
+        change: &ChangeOpt,
+        prefixes: &TagPrefixes,
+    ) -> Result<Self, Error> {
+        let patterns = prefixes.patterns();
         let mut args = vec!["describe", "--always", "--long", "--dirty", "--tags"];
 
+        // Only release tags are considered, leaving out the other tags of the repository
+        for pattern in &patterns {
+            args.extend(["--match", pattern]);
+        }
+
         if !change.include_merged_tags {
             args.push("--first-parent");
         }
@@ -44,8 +102,7 @@ impl ChangeData {
         let (_, description, _) = git(&metadata.workspace_root, &args)?;
 
         let sha_regex = Regex::new("^([0-9a-f]{7,40})(-dirty)?$").expect(INTERNAL_ERR);
-        let tag_regex =
-            Regex::new("^((?:.*@)?v?(.*))-(\\d+)-g([0-9a-f]{7,40})(-dirty)?$").expect(INTERNAL_ERR);
+        let tag_regex = Regex::new("^(.*)-(\\d+)-g([0-9a-f]{7,40})(-dirty)?$").expect(INTERNAL_ERR);
 
         let mut ret = Self::default();
 
@@ -61,12 +118,21 @@ impl ChangeData {
         } else if tag_regex.is_match(&description) {
             let caps = tag_regex.captures(&description).expect(INTERNAL_ERR);
 
-            ret.since = Some(caps.get(1).expect(INTERNAL_ERR).as_str().to_string());
-            ret.version = Some(caps.get(2).expect(INTERNAL_ERR).as_str().to_string());
+            let tag = caps.get(1).expect(INTERNAL_ERR).as_str();
+
+            let names = metadata
+                .packages
+                .iter()
+                .filter(|p| metadata.workspace_members.contains(&p.id))
+                .map(|p| p.name.as_str())
+                .collect::<Vec<_>>();
 
-            ret.sha = caps.get(4).expect(INTERNAL_ERR).as_str().to_string();
-            ret.dirty = caps.get(5).is_some();
-            ret.count = caps.get(3).expect(INTERNAL_ERR).as_str().to_string();
+            ret.since = Some(tag.to_string());
+            ret.version = prefixes.version(tag, &names).map(String::from);
+
+            ret.sha = caps.get(3).expect(INTERNAL_ERR).as_str().to_string();
+            ret.dirty = caps.get(4).is_some();
+            ret.count = caps.get(2).expect(INTERNAL_ERR).as_str().to_string();
         }
 
         Ok(ret)
@@ -74,48 +140,31 @@ impl ChangeData {
 }
 
 impl ChangeOpt {
-    pub fn get_changed_pkgs<'a>(
+    /// Whether the work that is not committed yet is taken into account
+    pub fn includes_uncommitted(&self) -> bool {
+        self.include_dirty || self.include_untracked
+    }
+
+    /// Changed and unchanged crates, along with the names of the crates changed only by
+    /// uncommitted work
+    pub fn get_changed_pkgs(
         &self,
         metadata: &Metadata,
         config: &WorkspaceConfig,
-        since: &Option<String>,
+        baselines: &Baselines,
         filter: &[GroupName],
         private: bool,
-    ) -> Result<
-        (
-            Vec<((GroupName, Option<Version>), Pkg)>,
-            Vec<((GroupName, Option<Version>), Pkg)>,
-        ),
-        Error,
-    > {
+    ) -> Result<(GroupedPkgs, GroupedPkgs, Vec<String>), Error> {
         let workspace_groups = get_group_packages(metadata, &config, private)?;
+        let mut uncommitted_only = vec![];
 
-        let pkgs = if let Some(since) = since {
+        let pkgs = if let Some(since) = &baselines.since {
             info!("looking for changes since", since);
 
-            let force = self
-                .force
-                .clone()
-                .map(|x| Glob::new(&x))
-                .map_or::<Result<_, GlobsetError>, _>(Ok(None), |x| Ok(x.ok()))?;
-            let ignore_changes = self
-                .ignore_changes
-                .clone()
-                .map(|x| Glob::new(&x))
-                .map_or::<Result<_, GlobsetError>, _>(Ok(None), |x| Ok(x.ok()))?;
-
-            let (_, changed_files, _) = git(
-                &metadata.workspace_root,
-                &["diff", "--name-only", "--relative", since],
-            )?;
-
-            let mut changed_files = changed_files
-                .split('\n')
-                .map(|f| f.trim())
-                .filter(|f| !f.is_empty())
-                .filter(|f| !matches!(&ignore_changes, Some(pattern) if pattern.compile_matcher().is_match(f)))
-                .map(|p| (Path::new(p), false))
-                .collect::<Vec<_>>();
+            // Options given in the workspace config add up to the command line ones
+            let force = glob_set(&[&self.force[..], &config.force[..]].concat())?;
+            let ignore_changes =
+                glob_set(&[&self.ignore_changes[..], &config.ignore_changes[..]].concat())?;
 
             let mut workspace_groups = workspace_groups.into_iter().collect::<Vec<_>>();
             workspace_groups.sort_by(|(_, a), (_, b)| {
@@ -125,34 +174,482 @@ impl ChangeOpt {
                     .cmp(&a.path.components().count())
             });
 
+            let mut changed_files = HashMap::new();
+
+            for baseline in std::iter::once(since).chain(baselines.tags.values().flatten()) {
+                if !changed_files.contains_key(baseline) {
+                    let files = self.changed_files(metadata, baseline, &ignore_changes)?;
+                    changed_files.insert(baseline.clone(), files);
+                }
+            }
+
+            let uncommitted = self.uncommitted_files(metadata, &ignore_changes)?;
+
+            let mut pkg_files = HashMap::new();
+
+            for ((group_name, _), p) in &workspace_groups {
+                if filter.is_empty() || filter.contains(group_name) {
+                    let files = PkgFiles::new(metadata, p, self.ignore_unpackaged)?;
+                    pkg_files.insert(p.name.clone(), files);
+                }
+            }
+
+            // Files belong to the most nested crate containing them
+            let mut claimed = vec![];
+
             workspace_groups
                 .into_iter()
                 .partition(|((group_name, _), p)| {
-                    if let Some(pattern) = &force {
-                        if pattern.compile_matcher().is_match(&p.name) {
-                            return true;
-                        }
+                    if force.is_match(&p.name) || p.config.force.unwrap_or_default() {
+                        return true;
                     }
 
                     if !(filter.is_empty() || filter.contains(&group_name)) {
                         return false;
                     }
 
-                    let mut has_changed = false;
-                    for (f, has_been_claimed) in changed_files.iter_mut() {
-                        if !*has_been_claimed {
-                            let just_changed = f.starts_with(&p.path) || p.path.as_os_str() == ".";
-                            *has_been_claimed = just_changed;
-                            has_changed |= just_changed;
+                    // Crates that were never released have changed
+                    let baseline = match baselines.of(&p.name) {
+                        Some(baseline) => baseline,
+                        None => {
+                            claimed.push(p.path.clone());
+                            return true;
                         }
+                    };
+
+                    let owns = |f: &PathBuf| {
+                        (f.starts_with(&p.path) || p.path.as_os_str() == ".")
+                            && !claimed.iter().any(|c| f.starts_with(c))
+                            && pkg_files[&p.name].counts(metadata, p, f)
+                    };
+
+                    // Watched files count even when they belong to other crates
+                    let affects = |f: &PathBuf| owns(f) || pkg_files[&p.name].watches(f);
+
+                    let has_changed = changed_files[baseline].iter().any(&affects);
+                    let has_uncommitted = !has_changed && uncommitted.iter().any(affects);
+
+                    if has_uncommitted {
+                        uncommitted_only.push(p.name.clone());
                     }
 
-                    has_changed
+                    claimed.push(p.path.clone());
+                    has_changed || has_uncommitted
                 })
         } else {
             (workspace_groups.into_iter().collect(), vec![])
         };
 
-        Ok(pkgs)
+        for ((group_name, _), p) in &pkgs.0 {
+            Event::PackageChanged {
+                name: &p.name,
+                version: &p.version,
+                path: &p.path,
+                group: group_name,
+            }
+            .emit()?;
+        }
+
+        Ok((pkgs.0, pkgs.1, uncommitted_only))
+    }
+
+    fn changed_files(
+        &self,
+        metadata: &Metadata,
+        since: &str,
+        ignore_changes: &GlobSet,
+    ) -> Result<Vec<PathBuf>, Error> {
+        let mut args = vec!["diff", "--name-only", "--relative", since];
+
+        // Uncommitted work is then looked at on its own, to tell the crates it changed apart
+        if self.includes_uncommitted() {
+            args.push("HEAD");
+        }
+
+        let (_, changed_files, _) = git(&metadata.workspace_root, &args)?;
+
+        Ok(changed_files
+            .split('\n')
+            .map(|f| f.trim())
+            .filter(|f| !f.is_empty())
+            .filter(|f| !ignore_changes.is_match(f))
+            .map(PathBuf::from)
+            .collect())
+    }
+
+    /// Files with uncommitted changes or untracked, as asked, relative to the workspace root
+    fn uncommitted_files(
+        &self,
+        metadata: &Metadata,
+        ignore_changes: &GlobSet,
+    ) -> Result<Vec<PathBuf>, Error> {
+        if !self.includes_uncommitted() {
+            return Ok(vec![]);
+        }
+
+        let untracked = if self.include_untracked {
+            "--untracked-files=all"
+        } else {
+            "--untracked-files=no"
+        };
+
+        let (_, prefix, _) = git(&metadata.workspace_root, &["rev-parse", "--show-prefix"])?;
+        let (_, status, _) = git(
+            &metadata.workspace_root,
+            &["status", "--porcelain=v2", "-z", untracked, "--", "."],
+        )?;
+
+        let mut files = vec![];
+        let mut entries = status.split('\0');
+
+        while let Some(entry) = entries.next() {
+            // Paths are the last field of the entries, and are relative to the repository root
+            let fields = match entry.chars().next() {
+                Some('1') => 9,
+                Some('2') => 10,
+                Some('u') => 11,
+                Some('?') => 2,
+                _ => continue,
+            };
+
+            let mut paths = vec![entry.splitn(fields, ' ').last().expect(INTERNAL_ERR)];
+
+            // Renamed and copied files are followed by their original path
+            if entry.starts_with('2') {
+                paths.extend(entries.next());
+            }
+
+            let untracked = entry.starts_with('?');
+
+            if untracked && !self.include_untracked || !untracked && !self.include_dirty {
+                continue;
+            }
+
+            for path in paths {
+                if let Some(path) = path.strip_prefix(prefix.as_str()) {
+                    if !ignore_changes.is_match(path) {
+                        files.push(PathBuf::from(path));
+                    }
+                }
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Last release of the workspace, along with the last individual tags of the crates, falling
+    /// back to the ones of the other crates in their custom group, which are released together,
+    /// and then to the last global tag
+    pub fn baselines(
+        &self,
+        metadata: &Metadata,
+        config: &WorkspaceConfig,
+        prefixes: &TagPrefixes,
+        private: bool,
+    ) -> Result<Baselines, Error> {
+        let since = ChangeData::new(metadata, self, prefixes)?.since;
+
+        if since.is_none() {
+            return Ok(Baselines::new(since));
+        }
+
+        let pkgs = get_group_packages(metadata, config, private)?
+            .into_iter()
+            .collect::<GroupedPkgs>();
+        let mut tags = HashMap::new();
+        let mut group_tags = HashMap::new();
+        let mut global_tag = None;
+
+        for ((group_name, _), pkg) in &pkgs {
+            let mut tag = self.last_tag(metadata, &[prefixes.individual_pattern(&pkg.name)])?;
+
+            if tag.is_none() && matches!(group_name, GroupName::Custom(_)) {
+                if !group_tags.contains_key(group_name) {
+                    let patterns = pkgs
+                        .iter()
+                        .filter(|((g, _), _)| g == group_name)
+                        .map(|(_, p)| prefixes.individual_pattern(&p.name))
+                        .collect::<Vec<_>>();
+
+                    group_tags.insert(group_name.clone(), self.last_tag(metadata, &patterns)?);
+                }
+
+                tag = group_tags[group_name].clone();
+            }
+
+            // The tags of the other crates do not tell whether this one was released
+            if tag.is_none() {
+                if global_tag.is_none() {
+                    let [global, _] = prefixes.patterns();
+                    global_tag = Some(self.last_tag(metadata, &[global])?);
+                }
+
+                tag = global_tag.clone().expect(INTERNAL_ERR);
+            }
+
+            debug!(
+                "baseline",
+                format!("{} {}", pkg.name, tag.as_deref().unwrap_or("none"))
+            );
+            tags.insert(pkg.name.clone(), tag);
+        }
+
+        Ok(Baselines { since, tags })
+    }
+
+    /// Most recent tag matching any of the patterns
+    fn last_tag(&self, metadata: &Metadata, patterns: &[String]) -> Result<Option<String>, Error> {
+        let mut args = vec!["describe", "--tags", "--abbrev=0"];
+
+        for pattern in patterns {
+            args.extend(["--match", pattern]);
+        }
+
+        if !self.include_merged_tags {
+            args.push("--first-parent");
+        }
+
+        let (status, tag, _) = git(&metadata.workspace_root, &args)?;
+
+        Ok(Some(tag).filter(|tag| status.success() && !tag.is_empty()))
+    }
+}
+
+/// Files of a crate whose changes are taken into account
+struct PkgFiles {
+    /// Files that would be packaged, when only those are considered
+    packaged: Option<HashSet<PathBuf>>,
+    ignored: GlobSet,
+    watched: GlobSet,
+}
+
+impl PkgFiles {
+    fn new(metadata: &Metadata, pkg: &Pkg, packaged: bool) -> Result<Self, Error> {
+        let packaged = if packaged {
+            let files = package_files(&metadata.workspace_root, &pkg.name, &pkg.manifest_path)?;
+            Some(files.into_iter().map(PathBuf::from).collect())
+        } else {
+            None
+        };
+
+        let mut ignored = GlobSetBuilder::new();
+
+        for pattern in &pkg.config.ignore_changes {
+            ignored.add(Glob::new(pattern)?);
+        }
+
+        let mut watched = GlobSetBuilder::new();
+
+        for pattern in &pkg.config.watch {
+            let pattern = workspace_pattern(&pkg.path, pattern).ok_or_else(|| {
+                Error::WatchOutsideWorkspace {
+                    pkg: pkg.name.clone(),
+                    pattern: pattern.clone(),
+                }
+            })?;
+
+            watched.add(Glob::new(&pattern)?);
+        }
+
+        Ok(Self {
+            packaged,
+            ignored: ignored.build()?,
+            watched: watched.build()?,
+        })
+    }
+
+    /// Whether the file, relative to the workspace root, is watched by the crate
+    fn watches(&self, file: &Path) -> bool {
+        self.watched.is_match(file)
+    }
+
+    /// Whether the change of the file, relative to the workspace root, is taken into account
+    fn counts(&self, metadata: &Metadata, pkg: &Pkg, file: &Path) -> bool {
+        let relative = file.strip_prefix(&pkg.path).unwrap_or(file);
+
+        if self.ignored.is_match(relative) {
+            return false;
+        }
+
+        match &self.packaged {
+            // Removed files may have been packaged, which is not known anymore
+            Some(packaged) => {
+                packaged.contains(relative)
+                    || !metadata.workspace_root.as_std_path().join(file).exists()
+            }
+            None => true,
+        }
+    }
+}
+
+/// Glob relative to the crate made relative to the workspace root, like the changed files,
+/// or `None` if it leaves the workspace
+fn workspace_pattern(pkg_path: &Path, pattern: &str) -> Option<String> {
+    let mut parts = pkg_path
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(part) => part.to_str(),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+
+    for part in pattern.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop()?;
+            }
+            _ => parts.push(part),
+        }
+    }
+
+    Some(parts.join("/"))
+}
+
+/// Why a crate is considered changed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "reason", rename_all = "kebab-case")]
+pub enum ChangeReason {
+    /// Files of the crate have changed
+    Direct,
+    /// The crate depends on a changed crate, directly or through others
+    Dependency { via: String },
+    /// Files of the crate have uncommitted changes or are untracked
+    Uncommitted,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ChangedPkg {
+    #[serde(flatten)]
+    pub pkg: Pkg,
+    #[serde(flatten)]
+    pub reason: ChangeReason,
+}
+
+impl Listable for Vec<(GroupName, ChangedPkg)> {
+    fn list(&self, list: ListOpt) -> Result<(), Error> {
+        if list.json || get_json_messages() {
+            return self.json();
+        }
+
+        list_pkgs(
+            self.iter()
+                .map(|(group_name, changed)| {
+                    let note = match &changed.reason {
+                        ChangeReason::Direct => None,
+                        ChangeReason::Uncommitted => {
+                            Some(style("(uncommitted)").black().bright().to_string())
+                        }
+                        ChangeReason::Dependency { via } => {
+                            Some(style(format!("(via {})", via)).black().bright().to_string())
+                        }
+                    };
+
+                    (group_name, &changed.pkg, note)
+                })
+                .collect(),
+            &list,
+        )
+    }
+}
+
+/// Crates affected by the changed crates because they depend on them, only directly unless
+/// `transitive` is given
+pub fn affected_pkgs(
+    metadata: &Metadata,
+    changed: &[(GroupName, ChangedPkg)],
+    unchanged: GroupedPkgs,
+    filter: &[GroupName],
+    transitive: bool,
+) -> Vec<(GroupName, ChangedPkg)> {
+    let graph = Graph::new(
+        metadata,
+        &changed
+            .iter()
+            .map(|(group_name, c)| (group_name.clone(), c.pkg.clone()))
+            .chain(
+                unchanged
+                    .iter()
+                    .map(|((group_name, _), pkg)| (group_name.clone(), pkg.clone())),
+            )
+            .collect::<Vec<_>>(),
+    );
+
+    let mut found = changed
+        .iter()
+        .map(|(_, c)| c.pkg.name.clone())
+        .collect::<Vec<_>>();
+    let mut affected = vec![];
+
+    let mut remaining = unchanged
+        .into_iter()
+        .filter(|((group_name, _), _)| filter.is_empty() || filter.contains(group_name))
+        .collect::<Vec<_>>();
+
+    loop {
+        let mut level = vec![];
+        let mut next = vec![];
+
+        for ((group_name, _), pkg) in remaining {
+            let via = graph
+                .dependencies(&pkg.name, &[], false)
+                .into_iter()
+                .find(|dep| found.iter().any(|x| x == dep))
+                .map(String::from);
+
+            match via {
+                Some(via) => level.push((
+                    group_name,
+                    ChangedPkg {
+                        pkg,
+                        reason: ChangeReason::Dependency { via },
+                    },
+                )),
+                None => next.push(((group_name, None), pkg)),
+            }
+        }
+
+        if level.is_empty() {
+            break;
+        }
+
+        found.extend(level.iter().map(|(_, c)| c.pkg.name.clone()));
+        affected.extend(level);
+
+        if !transitive {
+            break;
+        }
+
+        remaining = next;
+    }
+
+    affected
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_workspace_pattern() {
+        assert_eq!(
+            workspace_pattern(Path::new("crates/foo"), "../proto/**").as_deref(),
+            Some("crates/proto/**")
+        );
+        assert_eq!(
+            workspace_pattern(Path::new("foo"), "../assets/*.txt").as_deref(),
+            Some("assets/*.txt")
+        );
+        assert_eq!(
+            workspace_pattern(Path::new("."), "./build/*").as_deref(),
+            Some("build/*")
+        );
+
+        // Patterns cannot leave the workspace
+        assert_eq!(
+            workspace_pattern(Path::new("foo"), "../../assets/*.txt"),
+            None
+        );
+        assert_eq!(workspace_pattern(Path::new("."), "../*"), None);
     }
 }
@@ -0,0 +1,169 @@
+use crate::utils::{commit_messages, read_file, write_file, Error, GroupName, Pkg};
+
+use camino::Utf8PathBuf;
+use semver::Version;
+
+use std::path::{Path, PathBuf};
+
+pub const CHANGELOG: &str = "CHANGELOG.md";
+
+const TITLE: &str = "# Changelog";
+
+/// Released crates of a group, with their changelog entries
+pub type GroupEntries<'a> = Vec<(&'a Pkg, &'a Version, Vec<String>)>;
+
+/// Subject lines of the commits touching `path` since `since`
+pub fn changelog_entries(
+    root: &Utf8PathBuf,
+    since: Option<&str>,
+    path: &Path,
+) -> Result<Vec<String>, Error> {
+    Ok(commit_messages(root, since, path)?
+        .iter()
+        .filter_map(|msg| msg.lines().next())
+        .map(|subject| subject.trim().to_string())
+        .collect())
+}
+
+fn entries_list(entries: &[String], indent: &str) -> String {
+    if entries.is_empty() {
+        return format!("{}- Version bump only\n", indent);
+    }
+
+    entries
+        .iter()
+        .map(|entry| format!("{}- {}\n", indent, entry))
+        .collect()
+}
+
+pub fn package_section(version: &Version, entries: &[String]) -> String {
+    format!("## {}\n\n{}", version, entries_list(entries, ""))
+}
+
+pub fn workspace_section(
+    new_version: &Option<Version>,
+    groups: &[(GroupName, GroupEntries)],
+) -> String {
+    let mut section = format!(
+        "## {}\n",
+        new_version
+            .as_ref()
+            .map_or_else(|| "Release".to_string(), |x| x.to_string())
+    );
+
+    for (group_name, pkgs) in groups {
+        section.push_str(&format!("\n### {}\n\n", group_name));
+
+        for (pkg, version, entries) in pkgs {
+            section.push_str(&format!("- {}@{}\n", pkg.name, version));
+            section.push_str(&entries_list(entries, "  "));
+        }
+    }
+
+    section
+}
+
+/// Inserts `section` at the top of the changelog, right after its title
+pub fn prepend_section(changelog: &str, section: &str) -> String {
+    let rest = match changelog.strip_prefix(TITLE) {
+        Some(rest) if rest.is_empty() || rest.starts_with('\n') || rest.starts_with("\r\n") => rest,
+        _ => changelog,
+    };
+
+    let rest = rest.trim_start_matches(['\r', '\n']);
+
+    if rest.is_empty() {
+        format!("{}\n\n{}", TITLE, section)
+    } else {
+        format!("{}\n\n{}\n{}", TITLE, section, rest)
+    }
+}
+
+/// Writes `section` into the changelog at `path`, creating it if needed
+pub fn write_changelog(path: PathBuf, section: &str) -> Result<PathBuf, Error> {
+    let changelog = read_file(&path).unwrap_or_default();
+
+    write_file(&path, prepend_section(&changelog, section))?;
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_new_changelog() {
+        let section = package_section(
+            &Version::parse("0.2.0").unwrap(),
+            &["feat: foo".to_string(), "fix: bar".to_string()],
+        );
+
+        assert_eq!(
+            prepend_section("", &section),
+            indoc! {r#"
+                # Changelog
+
+                ## 0.2.0
+
+                - feat: foo
+                - fix: bar
+            "#}
+        );
+    }
+
+    #[test]
+    fn test_existing_changelog() {
+        let changelog = indoc! {r#"
+            # Changelog
+
+            ## 0.1.0
+
+            - Initial release
+        "#};
+
+        let section = package_section(&Version::parse("0.2.0").unwrap(), &[]);
+
+        assert_eq!(
+            prepend_section(changelog, &section),
+            indoc! {r#"
+                # Changelog
+
+                ## 0.2.0
+
+                - Version bump only
+
+                ## 0.1.0
+
+                - Initial release
+            "#}
+        );
+    }
+
+    #[test]
+    fn test_existing_changelog_without_title() {
+        let changelog = indoc! {r#"
+            ## 0.1.0
+
+            - Initial release
+        "#};
+
+        let section = package_section(&Version::parse("0.2.0").unwrap(), &[]);
+
+        assert_eq!(
+            prepend_section(changelog, &section),
+            indoc! {r#"
+                # Changelog
+
+                ## 0.2.0
+
+                - Version bump only
+
+                ## 0.1.0
+
+                - Initial release
+            "#}
+        );
+    }
+}
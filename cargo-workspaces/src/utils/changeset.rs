@@ -0,0 +1,215 @@
+use crate::utils::{create_file, get_dry_run, Error, ReleaseLevel};
+
+use camino::Utf8PathBuf;
+use clap::ArgEnum;
+
+use std::{
+    collections::BTreeMap as Map,
+    fmt, fs, io,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const CHANGESET_DIR: &str = ".changeset";
+
+const FENCE: &str = "---";
+
+/// Intended releases of crates along with a summary of the changes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Changeset {
+    pub releases: Map<String, ReleaseLevel>,
+    pub summary: String,
+}
+
+impl Changeset {
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut lines = content.lines();
+
+        if lines.next().map(str::trim) != Some(FENCE) {
+            return Err(format!("expected `{}` on the first line", FENCE));
+        }
+
+        let mut releases = Map::new();
+
+        loop {
+            let line = match lines.next() {
+                Some(line) => line.trim(),
+                None => return Err(format!("unterminated `{}` block", FENCE)),
+            };
+
+            if line == FENCE {
+                break;
+            }
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let (name, level) = line
+                .rsplit_once(':')
+                .ok_or_else(|| format!("expected `\"<crate>\": <level>`, found `{}`", line))?;
+
+            let name = name.trim().trim_matches(|c| c == '"' || c == '\'');
+            let level = ReleaseLevel::from_str(level.trim(), true)?;
+
+            if name.is_empty() {
+                return Err(format!("missing crate name in `{}`", line));
+            }
+
+            releases.insert(name.to_string(), level);
+        }
+
+        if releases.is_empty() {
+            return Err("no crates are released".to_string());
+        }
+
+        Ok(Self {
+            releases,
+            summary: lines.collect::<Vec<_>>().join("\n").trim().to_string(),
+        })
+    }
+}
+
+impl fmt::Display for Changeset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", FENCE)?;
+
+        for (name, level) in &self.releases {
+            writeln!(f, "\"{}\": {}", name, level)?;
+        }
+
+        writeln!(f, "{}\n\n{}", FENCE, self.summary)
+    }
+}
+
+/// Reads all the changesets in the workspace, ordered by their file names
+pub fn read_changesets(root: &Utf8PathBuf) -> Result<Vec<(PathBuf, Changeset)>, Error> {
+    let dir = root.join(CHANGESET_DIR);
+
+    if !dir.is_dir() {
+        return Ok(vec![]);
+    }
+
+    let mut paths = vec![];
+
+    for entry in fs::read_dir(&dir)? {
+        let path = entry?.path();
+
+        if path.extension().is_some_and(|x| x == "md")
+            && path.file_name().is_some_and(|x| x != "README.md")
+        {
+            paths.push(path);
+        }
+    }
+
+    paths.sort();
+
+    paths
+        .into_iter()
+        .map(|path| {
+            let changeset = Changeset::parse(&fs::read_to_string(&path)?).map_err(|msg| {
+                Error::InvalidChangeset {
+                    path: path.display().to_string(),
+                    msg,
+                }
+            })?;
+
+            Ok((path, changeset))
+        })
+        .collect()
+}
+
+/// Writes the changeset as `<id>.md`, generating an id if none is given, without replacing
+/// an existing one
+pub fn write_changeset(
+    root: &Utf8PathBuf,
+    id: Option<&str>,
+    changeset: &Changeset,
+) -> Result<PathBuf, Error> {
+    let dir = root.join(CHANGESET_DIR);
+
+    if !get_dry_run() {
+        fs::create_dir_all(&dir)?;
+    }
+
+    let id = id.map_or_else(
+        || {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            format!("{:x}", now.as_millis())
+        },
+        String::from,
+    );
+
+    let name = format!("{}/{}.md", CHANGESET_DIR, id);
+    let path = root.join(&name).into_std_path_buf();
+
+    // Another changeset can not be replaced, be it given the same name or generated at the same time
+    create_file(&path, changeset.to_string()).map_err(|err| match err.kind() {
+        io::ErrorKind::AlreadyExists => Error::ChangesetExists(name),
+        _ => err.into(),
+    })?;
+
+    Ok(path)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use indoc::indoc;
+
+    #[test]
+    fn test_roundtrip() {
+        let changeset = Changeset {
+            releases: vec![
+                ("dep1".to_string(), ReleaseLevel::Minor),
+                ("top".to_string(), ReleaseLevel::Patch),
+            ]
+            .into_iter()
+            .collect(),
+            summary: "Add a thing\n\nWith details".to_string(),
+        };
+
+        let content = changeset.to_string();
+
+        assert_eq!(
+            content,
+            indoc! {r#"
+                ---
+                "dep1": minor
+                "top": patch
+                ---
+
+                Add a thing
+
+                With details
+            "#}
+        );
+        assert_eq!(Changeset::parse(&content), Ok(changeset));
+    }
+
+    #[test]
+    fn test_parse_unquoted() {
+        let changeset = Changeset::parse(indoc! {r#"
+            ---
+            dep1: Major
+            'top': patch
+            ---
+            Fix it
+        "#})
+        .unwrap();
+
+        assert_eq!(changeset.releases["dep1"], ReleaseLevel::Major);
+        assert_eq!(changeset.releases["top"], ReleaseLevel::Patch);
+        assert_eq!(changeset.summary, "Fix it");
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(Changeset::parse("dep1: patch\n").is_err());
+        assert!(Changeset::parse("---\ndep1: patch\n").is_err());
+        assert!(Changeset::parse("---\ndep1: huge\n---\n").is_err());
+        assert!(Changeset::parse("---\n---\nNothing\n").is_err());
+    }
+}
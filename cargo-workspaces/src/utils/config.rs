@@ -4,7 +4,7 @@ use semver::Version;
 use serde::{de, Deserialize};
 use serde_json::{from_value, Value};
 
-use std::{fmt, path::Path};
+use std::{collections::BTreeMap as Map, fmt, path::Path};
 
 #[derive(Deserialize, Default)]
 struct MetadataWorkspaces<T> {
@@ -24,6 +24,13 @@ where
 #[derive(Deserialize, Default, Debug, Clone, Ord, Eq, PartialOrd, PartialEq)]
 pub struct PackageConfig {
     pub independent: Option<bool>,
+    pub force: Option<bool>,
+    #[serde(default)]
+    pub scripts: Map<String, String>,
+    #[serde(default)]
+    pub ignore_changes: Vec<String>,
+    #[serde(default)]
+    pub watch: Vec<String>,
 }
 
 #[derive(Deserialize, Debug)]
@@ -79,6 +86,14 @@ pub struct WorkspaceConfig {
     pub groups: Vec<WorkspaceGroupSpec>,
     pub allow_branch: Option<String>,
     pub no_individual_tags: Option<bool>,
+    pub tag_prefix: Option<String>,
+    pub individual_tag_prefix: Option<String>,
+    #[serde(default)]
+    pub force: Vec<String>,
+    #[serde(default)]
+    pub ignore_changes: Vec<String>,
+    #[serde(default)]
+    pub scripts: Map<String, String>,
 }
 
 fn deserialize_members<'de, D>(deserializer: D) -> Result<Vec<GroupMember>, D::Error>
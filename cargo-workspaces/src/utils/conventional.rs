@@ -0,0 +1,119 @@
+use crate::utils::{git, Error, ReleaseLevel, INTERNAL_ERR};
+
+use camino::Utf8PathBuf;
+use lazy_static::lazy_static;
+use regex::Regex;
+
+use std::path::Path;
+
+lazy_static! {
+    static ref HEADER: Regex =
+        Regex::new(r"^(?P<type>[A-Za-z]+)(?:\([^)]*\))?(?P<breaking>!)?:\s").expect(INTERNAL_ERR);
+    static ref BREAKING_FOOTER: Regex =
+        Regex::new(r"(?m)^BREAKING[ -]CHANGE:\s").expect(INTERNAL_ERR);
+}
+
+pub fn commit_level(message: &str) -> Option<ReleaseLevel> {
+    let caps = HEADER.captures(message.trim_start());
+
+    if caps.as_ref().is_some_and(|x| x.name("breaking").is_some())
+        || BREAKING_FOOTER.is_match(message)
+    {
+        return Some(ReleaseLevel::Major);
+    }
+
+    match caps?.name("type")?.as_str().to_lowercase().as_str() {
+        "feat" => Some(ReleaseLevel::Minor),
+        "fix" => Some(ReleaseLevel::Patch),
+        _ => None,
+    }
+}
+
+/// Reads the commits touching `path` since `since` (or since the beginning of history)
+pub fn commit_messages(
+    root: &Utf8PathBuf,
+    since: Option<&str>,
+    path: &Path,
+) -> Result<Vec<String>, Error> {
+    let range = since.map_or_else(|| "HEAD".to_string(), |since| format!("{}..HEAD", since));
+    let path = path.to_string_lossy();
+
+    let (_, log, _) = git(root, &["log", "--format=%B%x00", &range, "--", &path])?;
+
+    Ok(log
+        .split('\0')
+        .map(|x| x.trim())
+        .filter(|x| !x.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+/// Level of the release recommended by the commits touching `path`.
+/// Changed packages are always released with at least a patch bump.
+pub fn recommend_level(
+    root: &Utf8PathBuf,
+    since: Option<&str>,
+    path: &Path,
+) -> Result<ReleaseLevel, Error> {
+    Ok(commit_messages(root, since, path)?
+        .iter()
+        .filter_map(|msg| commit_level(msg))
+        .max()
+        .unwrap_or(ReleaseLevel::Patch))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::utils::version::Bump;
+
+    #[test]
+    fn test_fix() {
+        assert_eq!(commit_level("fix: typo"), Some(ReleaseLevel::Patch));
+    }
+
+    #[test]
+    fn test_feat_scope() {
+        assert_eq!(
+            commit_level("feat(parser): arrays"),
+            Some(ReleaseLevel::Minor)
+        );
+    }
+
+    #[test]
+    fn test_breaking_bang() {
+        assert_eq!(
+            commit_level("refactor!: drop api"),
+            Some(ReleaseLevel::Major)
+        );
+        assert_eq!(
+            commit_level("feat(api)!: drop api"),
+            Some(ReleaseLevel::Major)
+        );
+    }
+
+    #[test]
+    fn test_breaking_footer() {
+        let msg = "feat: new api\n\nBREAKING CHANGE: the old one is gone";
+        assert_eq!(commit_level(msg), Some(ReleaseLevel::Major));
+
+        let msg = "fix: new api\n\nBREAKING-CHANGE: the old one is gone";
+        assert_eq!(commit_level(msg), Some(ReleaseLevel::Major));
+    }
+
+    #[test]
+    fn test_other() {
+        assert_eq!(commit_level("chore: bump deps"), None);
+        assert_eq!(commit_level("Update README"), None);
+        assert_eq!(commit_level("feat add stuff"), None);
+    }
+
+    #[test]
+    fn test_bump_pre_1() {
+        let v = semver::Version::parse("0.3.1").unwrap();
+        assert!(matches!(ReleaseLevel::Major.bump(&v), Bump::Minor));
+
+        let v = semver::Version::parse("1.3.1").unwrap();
+        assert!(matches!(ReleaseLevel::Major.bump(&v), Bump::Major));
+    }
+}
@@ -0,0 +1,203 @@
+use crate::utils::{info, journaled, term_out, Error, Event, INTERNAL_ERR};
+
+use lazy_static::lazy_static;
+use oclif::term::TERM_ERR;
+use similar::TextDiff;
+
+use std::{
+    collections::BTreeMap as Map,
+    env,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
+};
+
+lazy_static! {
+    static ref DRY_RUN: AtomicBool = AtomicBool::new(false);
+    // Contents of the files as they would be after the run, `None` if removed
+    static ref OVERLAY: Mutex<Map<PathBuf, Option<String>>> = Mutex::new(Map::new());
+    static ref COMMANDS: Mutex<Vec<String>> = Mutex::new(vec![]);
+}
+
+pub fn get_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
+pub fn set_dry_run() {
+    DRY_RUN.store(true, Ordering::Relaxed);
+}
+
+/// Reads a file, taking into account the writes skipped by the dry run
+pub fn read_file<P: AsRef<Path>>(path: P) -> io::Result<String> {
+    if get_dry_run() {
+        if let Some(contents) = OVERLAY.lock().expect(INTERNAL_ERR).get(path.as_ref()) {
+            return contents
+                .clone()
+                .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "file removed in dry run"));
+        }
+    }
+
+    fs::read_to_string(path)
+}
+
+pub fn write_file<P: AsRef<Path>>(path: P, contents: String) -> io::Result<()> {
+    if get_dry_run() {
+        OVERLAY
+            .lock()
+            .unwrap()
+            .insert(path.as_ref().to_path_buf(), Some(contents));
+        return manifest_written(path.as_ref());
+    }
+
+    journaled(&path, |path| fs::write(path, contents))?;
+    manifest_written(path.as_ref())
+}
+
+/// Writes a new file, failing if it already exists
+pub fn create_file<P: AsRef<Path>>(path: P, contents: String) -> io::Result<()> {
+    let exists = || io::Error::new(io::ErrorKind::AlreadyExists, "file already exists");
+
+    if get_dry_run() {
+        let mut overlay = OVERLAY.lock().unwrap();
+        let path = path.as_ref();
+
+        if overlay.get(path).map_or(path.exists(), Option::is_some) {
+            return Err(exists());
+        }
+
+        overlay.insert(path.to_path_buf(), Some(contents));
+        drop(overlay);
+
+        return manifest_written(path);
+    }
+
+    journaled(&path, |path| {
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)?
+            .write_all(contents.as_bytes())
+    })?;
+    manifest_written(path.as_ref())
+}
+
+/// Reports the manifests written by any command, or that would be in a dry run
+fn manifest_written(path: &Path) -> io::Result<()> {
+    if path.file_name() != Some("Cargo.toml".as_ref()) {
+        return Ok(());
+    }
+
+    let event = if get_dry_run() {
+        Event::ManifestWouldWrite { path }
+    } else {
+        Event::ManifestWritten { path }
+    };
+
+    event
+        .emit()
+        .map_err(|err| io::Error::other(err.to_string()))
+}
+
+pub fn remove_file<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    if get_dry_run() {
+        OVERLAY
+            .lock()
+            .unwrap()
+            .insert(path.as_ref().to_path_buf(), None);
+        return Ok(());
+    }
+
+    journaled(path, |path| fs::remove_file(path))
+}
+
+/// Records the command instead of running it when in dry run mode
+pub fn would_run(program: &str, args: &[&str]) -> Result<bool, Error> {
+    if !get_dry_run() {
+        return Ok(false);
+    }
+
+    let mut redact = false;
+    let command = Some(program.to_string())
+        .into_iter()
+        .chain(args.iter().map(|arg| {
+            let arg = if redact {
+                "<redacted>".to_string()
+            } else if arg.is_empty() || arg.contains(char::is_whitespace) || arg.contains('"') {
+                format!("{:?}", arg)
+            } else {
+                arg.to_string()
+            };
+
+            redact = arg == "--token";
+            arg
+        }))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    info!("would run", command);
+
+    COMMANDS.lock().expect(INTERNAL_ERR).push(command);
+
+    Ok(true)
+}
+
+/// Prints the diffs of the files that would have been changed along with the skipped commands
+pub fn dry_run_summary() -> Result<(), Error> {
+    let cwd = env::current_dir()?;
+    let mut actions = vec![];
+
+    for (path, contents) in OVERLAY.lock().expect(INTERNAL_ERR).iter() {
+        let name = path
+            .strip_prefix(&cwd)
+            .unwrap_or(path)
+            .display()
+            .to_string();
+
+        let (action, old_header, new_header) = match (path.exists(), contents.is_some()) {
+            (false, false) => continue,
+            (false, true) => ("create", "/dev/null".to_string(), format!("b/{}", name)),
+            (true, false) => ("remove", format!("a/{}", name), "/dev/null".to_string()),
+            (true, true) => ("modify", format!("a/{}", name), format!("b/{}", name)),
+        };
+
+        let original = fs::read_to_string(path).unwrap_or_default();
+        let contents = contents.as_deref().unwrap_or_default();
+
+        if action == "modify" && original == contents {
+            continue;
+        }
+
+        term_out().write_str(
+            &TextDiff::from_lines(original.as_str(), contents)
+                .unified_diff()
+                .header(&old_header, &new_header)
+                .to_string(),
+        )?;
+
+        actions.push(format!("{} {}", action, name));
+    }
+
+    actions.extend(
+        COMMANDS
+            .lock()
+            .expect(INTERNAL_ERR)
+            .iter()
+            .map(|command| format!("run {}", command)),
+    );
+
+    if !actions.is_empty() {
+        TERM_ERR.write_line("\nDry run summary:")?;
+
+        for action in actions {
+            TERM_ERR.write_line(&format!(" - would {}", action))?;
+        }
+    }
+
+    info!("dry run", "no changes were made");
+
+    Ok(())
+}
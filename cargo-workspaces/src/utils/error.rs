@@ -97,6 +97,8 @@ pub enum Error {
         .0.iter().map(|group| format!("`{}`", group)).collect::<Vec<_>>().join(", ")
     )]
     UnmatchedExcludeGroupPattern(HashSet<String>),
+    #[error("the watched pattern `{pattern}` of package {pkg} goes outside of the workspace")]
+    WatchOutsideWorkspace { pkg: String, pattern: String },
     #[error("package {0}'s manifest has no parent directory")]
     ManifestHasNoParent(String),
     #[error("unable to read metadata specified in Cargo.toml: {0}")]
@@ -106,14 +108,25 @@ pub enum Error {
     Verify(String),
     #[error("unable to publish package {0}")]
     Publish(String),
+    #[error("unable to package {0}")]
+    Package(String),
     #[error("publishing has timed out")]
     PublishTimeout,
+    #[error("there is no unfinished release to resume")]
+    NoPublishState,
+    #[error("an unfinished release is recorded in {0}, continue it with `publish --resume` or delete the file to start over")]
+    PublishStateExists(String),
     #[error("unable to update Cargo.lock")]
     Update,
 
     #[error("{0} value must contain '%n'")]
     MustContainPercentN(String),
 
+    #[error("invalid changeset {path}: {msg}")]
+    InvalidChangeset { path: String, msg: String },
+    #[error("changeset {0} already exists")]
+    ChangesetExists(String),
+
     #[error("unable to create crate")]
     Create,
 
@@ -129,6 +142,8 @@ pub enum Error {
 
     #[error("child command failed to exit successfully")]
     Bail,
+    #[error("no crate defines the script {0}")]
+    ScriptNotFound(String),
 
     #[error("not a git repository")]
     NotGit,
@@ -155,6 +170,8 @@ pub enum Error {
 
     #[error("could not understand 'cargo config get' output: {0}")]
     BadConfigGetOutput(String),
+    #[error("unable to fetch {0} from the sparse index: {1}")]
+    SparseRegistry(String, String),
     #[error("crates index error: {0}")]
     CratesRegistry(#[from] crates_index::Error),
 
@@ -167,11 +184,75 @@ pub enum Error {
     #[error("{0}")]
     Serde(#[from] serde_json::Error),
     #[error("{0}")]
+    Toml(#[from] toml_edit::TomlError),
+    #[error("{0}")]
     Io(#[from] io::Error),
+    #[error("{0}")]
+    Metadata(#[from] cargo_metadata::Error),
     #[error("cannot convert command output to string, {0}")]
     FromUtf8(#[from] std::string::FromUtf8Error),
 }
 
+impl Error {
+    /// Stable identifier of the error, for machine-readable output
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::PackageNotInWorkspace { .. } => "package-not-in-workspace",
+            Self::PackageNotFound { .. } => "package-not-found",
+            Self::PackageExistsInMultipleGroups { .. } => "package-exists-in-multiple-groups",
+            Self::EmptyWorkspace => "empty-workspace",
+            Self::EmptyGroup { .. } => "empty-group",
+            Self::DuplicateGroupName { .. } => "duplicate-group-name",
+            Self::ReservedGroupName { .. } => "reserved-group-name",
+            Self::InvalidGroupName { .. } => "invalid-group-name",
+            Self::UnmatchedCustomGroupPattern(..) => "unmatched-custom-group-pattern",
+            Self::UnmatchedExcludeGroupPattern(..) => "unmatched-exclude-group-pattern",
+            Self::WatchOutsideWorkspace { .. } => "watch-outside-workspace",
+            Self::ManifestHasNoParent(..) => "manifest-has-no-parent",
+            Self::BadMetadata(..) => "bad-metadata",
+            Self::Verify(..) => "verify",
+            Self::Publish(..) => "publish",
+            Self::Package(..) => "package",
+            Self::PublishTimeout => "publish-timeout",
+            Self::NoPublishState => "no-publish-state",
+            Self::PublishStateExists(..) => "publish-state-exists",
+            Self::Update => "update",
+            Self::MustContainPercentN(..) => "must-contain-percent-n",
+            Self::InvalidChangeset { .. } => "invalid-changeset",
+            Self::ChangesetExists(..) => "changeset-exists",
+            Self::Create => "create",
+            Self::WorkspaceRootNotDir(..) => "workspace-root-not-dir",
+            Self::Init(..) => "init",
+            Self::Cargo { .. } => "cargo",
+            Self::Git { .. } => "git",
+            Self::Bail => "bail",
+            Self::ScriptNotFound(..) => "script-not-found",
+            Self::NotGit => "not-git",
+            Self::NoCommits => "no-commits",
+            Self::NotBranch => "not-branch",
+            Self::NoRemote { .. } => "no-remote",
+            Self::BehindRemote { .. } => "behind-remote",
+            Self::BranchNotAllowed { .. } => "branch-not-allowed",
+            Self::NotAdded(..) => "not-added",
+            Self::NotCommitted(..) => "not-committed",
+            Self::NotTagged(..) => "not-tagged",
+            Self::UnterminatedTagMsgScope(..) => "unterminated-tag-msg-scope",
+            Self::NotPushed(..) => "not-pushed",
+            Self::BadConfigGetOutput(..) => "bad-config-get-output",
+            Self::SparseRegistry(..) => "sparse-registry",
+            Self::CratesRegistry(..) => "crates-registry",
+            Self::Semver(..) => "semver",
+            Self::Glob(..) => "glob",
+            Self::Globset(..) => "globset",
+            Self::Serde(..) => "serde",
+            Self::Toml(..) => "toml",
+            Self::Io(..) => "io",
+            Self::Metadata(..) => "metadata",
+            Self::FromUtf8(..) => "from-utf8",
+        }
+    }
+}
+
 impl CliError for Error {
     fn color(self) -> Self {
         match self {
@@ -182,8 +263,26 @@ impl CliError for Error {
             Self::PackageNotFound { id } => Self::PackageNotFound {
                 id: format!("{}", ERR_YELLOW.apply_to(id)),
             },
+            Self::WatchOutsideWorkspace { pkg, pattern } => Self::WatchOutsideWorkspace {
+                pkg: format!("{}", ERR_YELLOW.apply_to(pkg)),
+                pattern,
+            },
+            Self::InvalidChangeset { path, msg } => Self::InvalidChangeset {
+                path: format!("{}", ERR_YELLOW.apply_to(path)),
+                msg,
+            },
+            Self::ChangesetExists(path) => {
+                Self::ChangesetExists(format!("{}", ERR_YELLOW.apply_to(path)))
+            }
             Self::Verify(pkg) => Self::Verify(format!("{}", ERR_YELLOW.apply_to(pkg))),
             Self::Publish(pkg) => Self::Publish(format!("{}", ERR_YELLOW.apply_to(pkg))),
+            Self::Package(pkg) => Self::Package(format!("{}", ERR_YELLOW.apply_to(pkg))),
+            Self::PublishStateExists(path) => {
+                Self::PublishStateExists(format!("{}", ERR_YELLOW.apply_to(path)))
+            }
+            Self::ScriptNotFound(name) => {
+                Self::ScriptNotFound(format!("{}", ERR_YELLOW.apply_to(name)))
+            }
             Self::MustContainPercentN(val) => {
                 Self::MustContainPercentN(format!("{}", ERR_YELLOW.apply_to(val)))
             }
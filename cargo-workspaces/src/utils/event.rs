@@ -0,0 +1,128 @@
+use crate::utils::{GroupName, Result};
+
+use clap::ArgEnum;
+use lazy_static::lazy_static;
+use oclif::{
+    console::Term,
+    term::{TERM_ERR, TERM_OUT},
+};
+use semver::Version;
+use serde::Serialize;
+
+use std::{
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+lazy_static! {
+    static ref JSON_MESSAGES: AtomicBool = AtomicBool::new(false);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum MessageFormat {
+    Human,
+    Json,
+}
+
+pub fn get_json_messages() -> bool {
+    JSON_MESSAGES.load(Ordering::Relaxed)
+}
+
+pub fn set_json_messages() {
+    JSON_MESSAGES.store(true, Ordering::Relaxed);
+}
+
+/// Terminal for the regular output, which moves to stderr when emitting events so that
+/// stdout only contains them
+pub fn term_out() -> &'static Term {
+    if get_json_messages() {
+        &TERM_ERR
+    } else {
+        &TERM_OUT
+    }
+}
+
+/// Events written to stdout as JSON lines with `--message-format json`
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum Event<'a> {
+    PackageChanged {
+        name: &'a str,
+        version: &'a Version,
+        path: &'a Path,
+        group: &'a GroupName,
+    },
+    VersionPlanned {
+        name: &'a str,
+        from: &'a Version,
+        to: &'a Version,
+    },
+    ManifestWritten {
+        path: &'a Path,
+    },
+    /// A manifest that is left untouched by the dry run
+    ManifestWouldWrite {
+        path: &'a Path,
+    },
+    TagCreated {
+        tag: &'a str,
+    },
+    Published {
+        name: &'a str,
+        version: &'a Version,
+    },
+    ExecStarted {
+        name: &'a str,
+        command: &'a [String],
+    },
+    ExecFinished {
+        name: &'a str,
+        code: Option<i32>,
+        success: bool,
+        duration_ms: u128,
+    },
+    Error {
+        code: &'static str,
+        message: String,
+    },
+}
+
+impl Event<'_> {
+    pub fn emit(&self) -> Result {
+        if get_json_messages() {
+            TERM_OUT.write_line(&serde_json::to_string(self)?)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_serialize() {
+        let version = Version::parse("1.2.3").unwrap();
+
+        assert_eq!(
+            serde_json::to_string(&Event::Published {
+                name: "dep1",
+                version: &version,
+            })
+            .unwrap(),
+            r#"{"event":"published","name":"dep1","version":"1.2.3"}"#
+        );
+
+        assert_eq!(
+            serde_json::to_string(&Event::ExecFinished {
+                name: "dep1",
+                code: Some(1),
+                success: false,
+                duration_ms: 12,
+            })
+            .unwrap(),
+            r#"{"event":"exec-finished","name":"dep1","code":1,"success":false,"duration_ms":12}"#
+        );
+    }
+}
@@ -0,0 +1,309 @@
+use crate::utils::{
+    dag, get_dry_run, get_json_messages, info, term_out, validate_jobs, would_run, Error, Event,
+    FilterOpt, GroupName, Pkg, Result, WorkspaceConfig, INTERNAL_ERR,
+};
+use cargo_metadata::{DependencyKind, Metadata, Package};
+use clap::Parser;
+use indexmap::IndexSet as Set;
+use oclif::{
+    console::{style, Term},
+    term::TERM_ERR,
+};
+use std::{
+    collections::HashMap as Map,
+    io::{BufRead, BufReader, Read},
+    process::{Command, ExitStatus, Stdio},
+    sync::mpsc,
+    thread,
+    time::{Duration, Instant},
+};
+
+#[derive(Debug, Parser)]
+pub struct ExecOpt {
+    /// Continue executing command despite non-zero exit in a given crate
+    #[clap(long)]
+    no_bail: bool,
+
+    /// Number of crates to execute the command in concurrently, once their dependencies are done
+    #[clap(short, long, default_value = "1", value_name = "N", validator = validate_jobs)]
+    jobs: usize,
+
+    /// Do not wait for the dependencies of a crate before executing the command in it
+    #[clap(long)]
+    no_topo: bool,
+
+    #[clap(flatten)]
+    pub filter: FilterOpt,
+}
+
+impl ExecOpt {
+    /// Runs the command given for each selected crate, skipping the crates without one
+    pub fn run(
+        &self,
+        metadata: &Metadata,
+        config: &WorkspaceConfig,
+        command: impl Fn(&Pkg) -> Option<Vec<String>>,
+    ) -> Result {
+        let selected = self
+            .filter
+            .select(metadata, config)?
+            .into_iter()
+            .filter_map(|(group_name, pkg)| {
+                let args = command(&pkg)?;
+                Some((pkg.id.clone(), (group_name, pkg, args)))
+            })
+            .collect::<Map<_, _>>();
+
+        let pkgs = metadata
+            .packages
+            .iter()
+            .filter(|x| selected.contains_key(&x.id))
+            .map(|x| (x, x.version.clone()))
+            .collect::<Vec<_>>();
+
+        let (names, visited) = dag(&pkgs);
+
+        let pkgs = visited
+            .iter()
+            .map(|p| names.get(p).expect(INTERNAL_ERR).0)
+            .collect::<Vec<_>>();
+
+        // Workspace crates that need to be done before executing in each crate
+        let deps = pkgs
+            .iter()
+            .map(|pkg| {
+                if self.no_topo {
+                    return vec![];
+                }
+
+                pkg.dependencies
+                    .iter()
+                    .filter(|d| matches!(d.kind, DependencyKind::Normal | DependencyKind::Build))
+                    .filter_map(|d| pkgs.iter().position(|p| p.name == d.name))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        let width = pkgs.iter().map(|p| p.name.len()).max().unwrap_or(0);
+
+        let mut results = vec![None; pkgs.len()];
+        let mut started = Set::new();
+        let mut done = Set::new();
+        let mut running = 0;
+        let mut failure = None;
+        let mut bail = false;
+
+        thread::scope(|scope| -> Result {
+            let (tx, rx) = mpsc::channel();
+
+            loop {
+                while failure.is_none() && !bail && running < self.jobs {
+                    let next = (0..pkgs.len()).find(|i| {
+                        !started.contains(i) && deps[*i].iter().all(|dep| done.contains(dep))
+                    });
+
+                    let i = match next {
+                        Some(i) => i,
+                        None => break,
+                    };
+
+                    let pkg = selected.get(&pkgs[i].id).expect(INTERNAL_ERR);
+                    let tx = tx.clone();
+                    let this = &self;
+
+                    started.insert(i);
+                    running += 1;
+
+                    scope.spawn(move || {
+                        let start = Instant::now();
+                        let result = this.exec(pkg, i, width);
+
+                        let _ = tx.send((i, result, start.elapsed()));
+                    });
+                }
+
+                if running == 0 {
+                    break;
+                }
+
+                let (i, result, elapsed) = rx.recv().expect(INTERNAL_ERR);
+                running -= 1;
+
+                match result {
+                    Ok(Some(status)) => {
+                        Event::ExecFinished {
+                            name: &pkgs[i].name,
+                            code: status.code(),
+                            success: status.success(),
+                            duration_ms: elapsed.as_millis(),
+                        }
+                        .emit()?;
+
+                        // Let the crates being executed finish but do not start new ones
+                        if !self.no_bail && !status.success() {
+                            bail = true;
+                        }
+
+                        results[i] = Some((status, elapsed));
+                    }
+                    Ok(None) => {}
+                    Err(err) => {
+                        failure.get_or_insert(err);
+                    }
+                }
+
+                done.insert(i);
+            }
+
+            Ok(())
+        })?;
+
+        if let Some(err) = failure {
+            return Err(err);
+        }
+
+        if !get_dry_run() && self.jobs > 1 && !pkgs.is_empty() {
+            summary(&pkgs, &results, width)?;
+        }
+
+        if bail {
+            return Err(Error::Bail);
+        }
+
+        info!("success", "ok");
+        Ok(())
+    }
+
+    /// Executes the command in the crate, returning nothing in a dry run
+    fn exec(
+        &self,
+        (group_name, pkg, args): &(GroupName, Pkg, Vec<String>),
+        index: usize,
+        width: usize,
+    ) -> Result<Option<ExitStatus>> {
+        let dir = pkg
+            .manifest_path
+            .parent()
+            .ok_or_else(|| Error::ManifestHasNoParent(pkg.name.clone()))?;
+
+        if would_run(
+            &format!("(in {}) {}", pkg.name, args[0]),
+            &args[1..].iter().map(|x| x.as_str()).collect::<Vec<_>>(),
+        )? {
+            return Ok(None);
+        }
+
+        Event::ExecStarted {
+            name: &pkg.name,
+            command: args,
+        }
+        .emit()?;
+
+        let mut cmd = Command::new(args.get(0).expect(INTERNAL_ERR));
+        cmd.args(&args[1..])
+            .current_dir(dir)
+            .env("CARGO_WS_PKG_NAME", &pkg.name)
+            .env("CARGO_WS_PKG_VERSION", pkg.version.to_string())
+            .env("CARGO_WS_PKG_PATH", &pkg.path)
+            .env("CARGO_WS_PKG_MANIFEST_PATH", &pkg.manifest_path)
+            .env("CARGO_WS_PKG_PRIVATE", pkg.private.to_string())
+            .env("CARGO_WS_GROUP", group_name.to_string())
+            .env("CARGO_WS_TOPO_INDEX", index.to_string());
+
+        // Without concurrent crates, the output can go straight to the terminal
+        if self.jobs == 1 && !get_json_messages() {
+            return Ok(Some(cmd.status()?));
+        }
+
+        let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+
+        let name = format!("{:w$}", pkg.name, w = width);
+        let stdout = child.stdout.take().expect(INTERNAL_ERR);
+        let stderr = child.stderr.take().expect(INTERNAL_ERR);
+
+        // Output goes to stderr when emitting events, which keeps stdout for them
+        thread::scope(|scope| {
+            let out =
+                scope.spawn(|| prefix_lines(stdout, term_out(), &style(&name).cyan().to_string()));
+
+            let err = prefix_lines(
+                stderr,
+                &TERM_ERR,
+                &style(&name).cyan().for_stderr().to_string(),
+            );
+
+            out.join().expect(INTERNAL_ERR).and(err)
+        })?;
+
+        Ok(Some(child.wait()?))
+    }
+}
+
+/// Copies the output of a crate line by line, so that concurrent crates do not interleave
+fn prefix_lines(reader: impl Read, term: &Term, prefix: &str) -> Result {
+    let mut reader = BufReader::new(reader);
+    let mut line = vec![];
+
+    while reader.read_until(b'\n', &mut line)? > 0 {
+        let text = String::from_utf8_lossy(&line);
+        let text = text.trim_end_matches(&['\n', '\r'][..]);
+
+        if text.is_empty() {
+            term.write_line(&format!("{} |", prefix))?;
+        } else {
+            term.write_line(&format!("{} | {}", prefix, text))?;
+        }
+
+        line.clear();
+    }
+
+    Ok(())
+}
+
+fn summary(pkgs: &[&Package], results: &[Option<(ExitStatus, Duration)>], width: usize) -> Result {
+    let width = width.max("crate".len());
+
+    TERM_ERR.write_line("")?;
+    TERM_ERR.write_line(&format!(
+        "{:w$}  {:>6}  {:>8}",
+        "crate",
+        "exit",
+        "time",
+        w = width
+    ))?;
+
+    for (pkg, result) in pkgs.iter().zip(results) {
+        let (code, time) = match result {
+            Some((status, elapsed)) => {
+                let code = status
+                    .code()
+                    .map_or("signal".to_string(), |c| c.to_string());
+                let code = format!("{:>6}", code);
+
+                (
+                    if status.success() {
+                        style(code).green()
+                    } else {
+                        style(code).red()
+                    },
+                    format!("{:.2}s", elapsed.as_secs_f64()),
+                )
+            }
+            None => (
+                style(format!("{:>6}", "-")).black().bright(),
+                "-".to_string(),
+            ),
+        };
+
+        TERM_ERR.write_line(&format!(
+            "{:w$}  {}  {:>8}",
+            pkg.name,
+            code.for_stderr(),
+            time,
+            w = width
+        ))?;
+    }
+
+    TERM_ERR.write_line("")?;
+    Ok(())
+}
@@ -0,0 +1,97 @@
+use crate::utils::{
+    get_group_packages, Baselines, ChangeOpt, GroupName, Pkg, Result, TagPrefixes, WorkspaceConfig,
+};
+use cargo_metadata::Metadata;
+use clap::Parser;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+#[derive(Debug, Parser)]
+pub struct FilterOpt {
+    /// Skip private crates
+    #[clap(long)]
+    pub no_private: bool,
+
+    /// Comma separated list of crate groups to deal with
+    #[clap(
+        long,
+        multiple_occurrences = true,
+        use_value_delimiter = true,
+        number_of_values = 1
+    )]
+    pub groups: Vec<GroupName>,
+
+    /// Only include crates whose names match the glob
+    #[clap(long, value_name = "pattern", multiple_occurrences = true)]
+    pub include: Vec<String>,
+
+    /// Skip crates whose names match the glob
+    #[clap(long, value_name = "pattern", multiple_occurrences = true)]
+    pub ignore: Vec<String>,
+
+    /// Only include crates that have changed since the last tagged release
+    #[clap(long)]
+    pub changed: bool,
+
+    /// Only include crates that have changed since this git reference
+    #[clap(
+        long,
+        value_name = "ref",
+        conflicts_with = "include-merged-tags",
+        forbid_empty_values(true)
+    )]
+    pub since: Option<String>,
+
+    #[clap(flatten)]
+    pub change: ChangeOpt,
+}
+
+impl FilterOpt {
+    /// Selected crates of the workspace along with their group names
+    pub fn select(
+        &self,
+        metadata: &Metadata,
+        config: &WorkspaceConfig,
+    ) -> Result<Vec<(GroupName, Pkg)>> {
+        let pkgs = if self.changed || self.since.is_some() {
+            let baselines = match &self.since {
+                Some(since) => Baselines::new(Some(since.clone())),
+                None => {
+                    let prefixes = TagPrefixes::new(config, None, None)?;
+                    self.change
+                        .baselines(metadata, config, &prefixes, !self.no_private)?
+                }
+            };
+
+            self.change
+                .get_changed_pkgs(metadata, config, &baselines, &self.groups, !self.no_private)?
+                .0
+        } else {
+            get_group_packages(metadata, config, !self.no_private)?
+                .into_iter()
+                .filter(|((group_name, _), _)| {
+                    self.groups.is_empty() || self.groups.contains(group_name)
+                })
+                .collect()
+        };
+
+        let include = glob_set(&self.include)?;
+        let ignore = glob_set(&self.ignore)?;
+
+        Ok(pkgs
+            .into_iter()
+            .filter(|(_, pkg)| self.include.is_empty() || include.is_match(&pkg.name))
+            .filter(|(_, pkg)| !ignore.is_match(&pkg.name))
+            .map(|((group_name, _), pkg)| (group_name, pkg))
+            .collect())
+    }
+}
+
+pub fn glob_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+
+    for pattern in patterns {
+        builder.add(Glob::new(pattern)?);
+    }
+
+    Ok(builder.build()?)
+}
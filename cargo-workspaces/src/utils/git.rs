@@ -1,4 +1,7 @@
-use crate::utils::{debug, info, validate_value_containing_name, Error, Pkg, WorkspaceConfig};
+use crate::utils::{
+    debug, info, validate_value_containing_name, would_run, Error, Event, Pkg, WorkspaceConfig,
+    INTERNAL_ERR,
+};
 
 use camino::Utf8PathBuf;
 use clap::Parser;
@@ -8,7 +11,10 @@ use semver::Version;
 
 use std::{
     collections::BTreeMap as Map,
+    fmt,
+    path::PathBuf,
     process::{Command, ExitStatus},
+    sync::Mutex,
 };
 
 pub fn git<'a>(
@@ -17,6 +23,10 @@ pub fn git<'a>(
 ) -> Result<(ExitStatus, String, String), Error> {
     debug!("git", args.to_vec().join(" "));
 
+    if is_mutating(args) && would_run("git", args)? {
+        return Ok((ExitStatus::default(), String::new(), String::new()));
+    }
+
     let output = Command::new("git")
         .current_dir(root)
         .args(args)
@@ -33,6 +43,95 @@ pub fn git<'a>(
     ))
 }
 
+/// Whether the git command changes the repository or its remote
+fn is_mutating(args: &[&str]) -> bool {
+    match args {
+        ["add" | "commit" | "push" | "rm", ..] => true,
+        // Tags are created by name or with options, while the other options list them
+        ["tag", arg, ..] => {
+            !arg.starts_with('-')
+                || matches!(
+                    *arg,
+                    "-a" | "--annotate"
+                        | "-s"
+                        | "--sign"
+                        | "-m"
+                        | "-f"
+                        | "--force"
+                        | "-d"
+                        | "--delete"
+                )
+        }
+        _ => false,
+    }
+}
+
+/// Prefixes of the release tags, which tell them apart from the other tags of the repository
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagPrefixes {
+    pub global: String,
+    pub individual: String,
+}
+
+impl TagPrefixes {
+    pub fn new(
+        config: &WorkspaceConfig,
+        global: Option<&str>,
+        individual: Option<&str>,
+    ) -> Result<Self, Error> {
+        let individual = individual
+            .or(config.individual_tag_prefix.as_deref())
+            .unwrap_or("%n@");
+
+        validate_value_containing_name(individual)
+            .map_err(|_| Error::MustContainPercentN("individual_tag_prefix".into()))?;
+
+        Ok(Self {
+            global: global
+                .or(config.tag_prefix.as_deref())
+                .unwrap_or("v")
+                .to_string(),
+            individual: individual.to_string(),
+        })
+    }
+
+    pub fn global_tag(&self, version: &impl fmt::Display) -> String {
+        format!("{}{}", self.global, version)
+    }
+
+    pub fn individual_tag(&self, name: &str, version: &impl fmt::Display) -> String {
+        format!("{}{}", self.individual.replace("%n", name), version)
+    }
+
+    /// Patterns for `git describe --match` matching the global tags and the individual tags
+    pub fn patterns(&self) -> [String; 2] {
+        [
+            format!("{}[0-9]*", self.global),
+            format!("{}[0-9]*", self.individual.replace("%n", "*")),
+        ]
+    }
+
+    /// Pattern for `git describe --match` matching the individual tags of the crate
+    pub fn individual_pattern(&self, name: &str) -> String {
+        format!("{}[0-9]*", self.individual.replace("%n", name))
+    }
+
+    /// Version released with the tag, if it is a global tag or an individual tag of the crates
+    pub fn version<'a>(&self, tag: &'a str, names: &[&str]) -> Option<&'a str> {
+        let is_version = |v: &&str| Version::parse(v).is_ok();
+
+        if let Some(version) = tag.strip_prefix(&self.global).filter(is_version) {
+            return Some(version);
+        }
+
+        // The name is matched as a whole, since the prefix can end right before the version
+        names.iter().find_map(|name| {
+            tag.strip_prefix(&self.individual.replace("%n", name))
+                .filter(is_version)
+        })
+    }
+}
+
 #[derive(Debug, Parser)]
 #[clap(next_help_heading = "GIT OPTIONS")]
 pub struct GitOpt {
@@ -73,19 +172,18 @@ pub struct GitOpt {
     #[clap(long)]
     pub tag_private: bool,
 
-    /// Customize tag prefix for global tags (can be empty)
-    #[clap(long, default_value = "v", value_name = "prefix")]
-    pub tag_prefix: String,
+    /// Customize tag prefix for global tags (can be empty) [default: v]
+    #[clap(long, value_name = "prefix")]
+    pub tag_prefix: Option<String>,
 
-    /// Customize prefix for individual tags (should contain `%n`)
+    /// Customize prefix for individual tags (should contain `%n`) [default: %n@]
     #[clap(
         long,
-        default_value = "%n@",
         value_name = "prefix",
         validator = validate_value_containing_name,
         forbid_empty_values(true)
     )]
-    pub individual_tag_prefix: String,
+    pub individual_tag_prefix: Option<String>,
 
     /// Customize tag msg, defaults to tag name (can contain `%v`)
     #[clap(long, value_name = "msg", multiple_occurrences = true)]
@@ -116,6 +214,10 @@ pub struct GitOpt {
         "tag-msg", "individual-tag-msg", "no-git-push", "git-remote"
     ])]
     pub no_git: bool,
+
+    /// Tags created in this run, which are the only ones rolled back on failure
+    #[clap(skip)]
+    created_tags: Mutex<Vec<String>>,
 }
 
 impl GitOpt {
@@ -223,6 +325,8 @@ impl GitOpt {
         root: &Utf8PathBuf,
         new_version: &Option<Version>,
         new_versions: &Map<String, (Pkg, Version)>,
+        files: &[PathBuf],
+        notes: &[String],
     ) -> Result<(), Error> {
         if self.no_git || self.no_git_commit {
             return Ok(());
@@ -236,6 +340,22 @@ impl GitOpt {
             return Err(Error::NotAdded(added.1, added.2));
         }
 
+        if !files.is_empty() {
+            let files = files
+                .iter()
+                .map(|x| x.to_string_lossy())
+                .collect::<Vec<_>>();
+
+            let mut args = vec!["add", "--"];
+            args.extend(files.iter().map(|x| x.as_ref()));
+
+            let added = git(root, &args)?;
+
+            if !added.0.success() {
+                return Err(Error::NotAdded(added.1, added.2));
+            }
+        }
+
         let mut args = vec!["commit".to_string()];
 
         if self.amend {
@@ -250,7 +370,7 @@ impl GitOpt {
                 msg = supplied;
             }
 
-            let mut msg = self.commit_msg(msg, new_versions);
+            let mut msg = self.commit_msg(msg, new_versions, notes);
 
             msg = msg.replace(
                 "%v",
@@ -271,17 +391,27 @@ impl GitOpt {
         Ok(())
     }
 
+    /// Tag prefixes given as options, falling back to the workspace config
+    pub fn tag_prefixes(&self, config: &WorkspaceConfig) -> Result<TagPrefixes, Error> {
+        TagPrefixes::new(
+            config,
+            self.tag_prefix.as_deref(),
+            self.individual_tag_prefix.as_deref(),
+        )
+    }
+
     pub fn global_tag(
         &self,
         root: &Utf8PathBuf,
         new_version: &Version,
         new_versions: &Map<String, (Pkg, Version)>,
+        config: &WorkspaceConfig,
     ) -> Result<Option<String>, Error> {
         if self.no_git || self.no_git_tag || self.no_global_tag {
             return Ok(None);
         }
 
-        let tag = format!("{}{}", &self.tag_prefix, new_version);
+        let tag = self.tag_prefixes(config)?.global_tag(new_version);
         let mut msgs = Vec::with_capacity(self.tag_msg.capacity().max(1));
         for msg in &self.tag_msg {
             let mut s = String::new();
@@ -332,11 +462,9 @@ impl GitOpt {
             return Ok(None);
         }
 
-        let tag = format!(
-            "{}{}",
-            self.individual_tag_prefix.replace("%n", pkg_name),
-            new_version
-        );
+        let tag = self
+            .tag_prefixes(config)?
+            .individual_tag(pkg_name, &new_version);
         let msg = self.individual_tag_msg.as_ref().map_or(tag.clone(), |msg| {
             msg.replace("%n", pkg_name).replace("%v", new_version)
         });
@@ -376,12 +504,51 @@ impl GitOpt {
         let pushed = git(root, &args)?;
 
         if !pushed.0.success() {
+            // Keep the tags in sync with the remote so that the release can be retried
+            self.remove_created_tags(root)?;
             return Err(Error::NotPushed(pushed.1, pushed.2));
         }
 
         Ok(())
     }
 
+    /// Removes the given files from the index, keeping them in the working tree
+    pub fn unstage(&self, root: &Utf8PathBuf, files: &[PathBuf]) -> Result<(), Error> {
+        if self.no_git || self.no_git_commit || files.is_empty() {
+            return Ok(());
+        }
+
+        let files = files
+            .iter()
+            .map(|x| x.to_string_lossy())
+            .collect::<Vec<_>>();
+
+        let mut args = vec!["reset", "-q", "--"];
+        args.extend(files.iter().map(|x| x.as_ref()));
+
+        git(root, &args)?;
+
+        Ok(())
+    }
+
+    /// Deletes the tags created in this run, leaving the ones that already existed
+    pub fn remove_created_tags(&self, root: &Utf8PathBuf) -> Result<(), Error> {
+        let tags = std::mem::take(&mut *self.created_tags.lock().expect(INTERNAL_ERR));
+
+        if tags.is_empty() {
+            return Ok(());
+        }
+
+        info!("git", format!("removing tags {}", tags.join(", ")));
+
+        let mut args = vec!["tag", "-d"];
+        args.extend(tags.iter().map(|x| x.as_str()));
+
+        git(root, &args)?;
+
+        Ok(())
+    }
+
     fn tag(&self, root: &Utf8PathBuf, tag: &str, msgs: &[String]) -> Result<(), Error> {
         let (_, tags, _) = git(root, &["tag"])?;
         if let None = tags.split("\n").find(|existing_tag| &tag == existing_tag) {
@@ -394,8 +561,16 @@ impl GitOpt {
             let tagged = git(root, &args)?;
 
             if !tagged.0.success() {
+                self.remove_created_tags(root)?;
                 return Err(Error::NotTagged(tag.to_string(), tagged.1, tagged.2));
             }
+
+            self.created_tags
+                .lock()
+                .expect(INTERNAL_ERR)
+                .push(tag.to_string());
+
+            Event::TagCreated { tag }.emit()?;
         } else {
             info!(
                 "git",
@@ -405,15 +580,70 @@ impl GitOpt {
         Ok(())
     }
 
-    fn commit_msg(&self, msg: &str, new_versions: &Map<String, (Pkg, Version)>) -> String {
+    fn commit_msg(
+        &self,
+        msg: &str,
+        new_versions: &Map<String, (Pkg, Version)>,
+        notes: &[String],
+    ) -> String {
+        let notes = notes
+            .iter()
+            .map(|x| format!("\n\n{}", x))
+            .collect::<String>();
+
         format!(
-            "{}\n\n{}\n\nGenerated by cargo-workspaces",
+            "{}\n\n{}{}\n\nGenerated by cargo-workspaces",
             msg,
             new_versions
                 .iter()
                 .map(|x| format!("{}@{}", x.0, x.1 .1))
                 .collect::<Vec<_>>()
-                .join("\n")
+                .join("\n"),
+            notes
         )
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn prefixes(global: &str, individual: &str) -> TagPrefixes {
+        TagPrefixes {
+            global: global.to_string(),
+            individual: individual.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_is_mutating() {
+        assert!(is_mutating(&["tag", "v1.0.0", "-a", "-m", "v1.0.0"]));
+        assert!(is_mutating(&["tag", "-a", "v1.0.0"]));
+        assert!(is_mutating(&["tag", "-d", "v1.0.0"]));
+        assert!(!is_mutating(&["tag"]));
+        assert!(!is_mutating(&["tag", "-l"]));
+        assert!(!is_mutating(&["tag", "--list", "v*"]));
+        assert!(!is_mutating(&["describe", "--tags"]));
+    }
+
+    #[test]
+    fn test_tag_version() {
+        let default = prefixes("v", "%n@");
+
+        assert_eq!(default.version("v1.2.3", &[]), Some("1.2.3"));
+        assert_eq!(default.version("dep1@0.1.0", &["dep1"]), Some("0.1.0"));
+        assert_eq!(default.version("dep2@0.1.0", &["dep1"]), None);
+        assert_eq!(default.version("docs-2024", &["docs"]), None);
+
+        let custom = prefixes("release-", "%n/v");
+
+        assert_eq!(custom.version("release-1.0.0", &[]), Some("1.0.0"));
+        assert_eq!(custom.version("dep-1/v0.2.0", &["dep-1"]), Some("0.2.0"));
+        assert_eq!(custom.version("v1.0.0", &[]), None);
+
+        let bare = prefixes("v", "%n");
+
+        assert_eq!(bare.version("dep10.1.0", &["dep1"]), Some("0.1.0"));
+        assert_eq!(bare.version("dep10.1.0", &["dep10"]), None);
+    }
+}
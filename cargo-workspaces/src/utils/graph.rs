@@ -0,0 +1,327 @@
+use crate::utils::{
+    get_group_packages, get_json_messages, read_config, term_out, Error, GroupName, ListOpt, Pkg,
+    Result, WorkspaceConfig,
+};
+
+use cargo_metadata::{DependencyKind, Metadata};
+use clap::ArgEnum;
+use oclif::term::TERM_OUT;
+use semver::Version;
+use serde::Serialize;
+
+use std::{collections::BTreeSet as Set, fmt::Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum GraphFormat {
+    Dot,
+    Mermaid,
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, ArgEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DepKind {
+    Normal,
+    Build,
+    Dev,
+}
+
+impl DepKind {
+    pub fn new(kind: DependencyKind) -> Option<Self> {
+        match kind {
+            DependencyKind::Normal => Some(Self::Normal),
+            DependencyKind::Build => Some(Self::Build),
+            DependencyKind::Development => Some(Self::Dev),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Normal => "normal",
+            Self::Build => "build",
+            Self::Dev => "dev",
+        }
+    }
+}
+
+/// Crates related to the given crate in the graph of the whole workspace, filtered like a list
+pub fn related_pkgs(
+    metadata: &Metadata,
+    list: &ListOpt,
+    name: &str,
+    related: impl for<'a> Fn(&'a Graph) -> Set<&'a str>,
+) -> Result<Vec<(GroupName, Pkg)>> {
+    let config: WorkspaceConfig = read_config(&metadata.workspace_metadata)?;
+
+    let pkgs = get_group_packages(metadata, &config, true)?
+        .into_iter()
+        .map(|((group_name, _), pkg)| (group_name, pkg))
+        .collect::<Vec<_>>();
+
+    if !pkgs.iter().any(|(_, p)| p.name == name) {
+        return Err(Error::PackageNotFound {
+            id: name.to_string(),
+        });
+    }
+
+    let graph = Graph::new(metadata, &pkgs);
+    let names = related(&graph);
+
+    Ok(pkgs
+        .into_iter()
+        .filter(|(_, pkg)| names.contains(pkg.name.as_str()))
+        .filter(|(_, pkg)| list.all || !pkg.private)
+        .filter(|(group_name, _)| list.groups.is_empty() || list.groups.contains(group_name))
+        .collect())
+}
+
+/// Dependencies between the crates of the workspace
+#[derive(Debug, Serialize)]
+pub struct Graph {
+    pub nodes: Vec<Node>,
+    pub edges: Vec<Edge>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Node {
+    pub name: String,
+    pub version: Version,
+    pub group: GroupName,
+    pub private: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Edge {
+    pub from: String,
+    pub to: String,
+    pub kind: DepKind,
+}
+
+impl Graph {
+    /// Graph of the given crates, leaving out the dependencies on other crates
+    pub fn new(metadata: &Metadata, pkgs: &[(GroupName, Pkg)]) -> Self {
+        let mut edges = Set::new();
+
+        for (_, pkg) in pkgs {
+            let package = match metadata.packages.iter().find(|p| p.id == pkg.id) {
+                Some(package) => package,
+                None => continue,
+            };
+
+            for d in &package.dependencies {
+                if !pkgs.iter().any(|(_, p)| p.name == d.name) {
+                    continue;
+                }
+
+                if let Some(kind) = DepKind::new(d.kind) {
+                    edges.insert(Edge {
+                        from: pkg.name.clone(),
+                        to: d.name.clone(),
+                        kind,
+                    });
+                }
+            }
+        }
+
+        Self {
+            nodes: pkgs
+                .iter()
+                .map(|(group_name, pkg)| Node {
+                    name: pkg.name.clone(),
+                    version: pkg.version.clone(),
+                    group: group_name.clone(),
+                    private: pkg.private,
+                })
+                .collect(),
+            edges: edges.into_iter().collect(),
+        }
+    }
+
+    /// Crates depending on the given crate through the given kinds of dependencies
+    pub fn dependents(&self, name: &str, kinds: &[DepKind], transitive: bool) -> Set<&str> {
+        self.walk(name, kinds, transitive, |e| (&e.to, &e.from))
+    }
+
+    /// Crates the given crate depends on through the given kinds of dependencies
+    pub fn dependencies(&self, name: &str, kinds: &[DepKind], transitive: bool) -> Set<&str> {
+        self.walk(name, kinds, transitive, |e| (&e.from, &e.to))
+    }
+
+    fn walk<'a>(
+        &'a self,
+        name: &str,
+        kinds: &[DepKind],
+        transitive: bool,
+        direction: impl Fn(&'a Edge) -> (&'a String, &'a String),
+    ) -> Set<&'a str> {
+        let mut found = Set::new();
+        let mut queue = vec![name.to_string()];
+
+        while let Some(current) = queue.pop() {
+            for edge in &self.edges {
+                if !kinds.is_empty() && !kinds.contains(&edge.kind) {
+                    continue;
+                }
+
+                let (from, to) = direction(edge);
+
+                if *from == current && to != name && found.insert(to.as_str()) && transitive {
+                    queue.push(to.clone());
+                }
+            }
+        }
+
+        found
+    }
+
+    pub fn print(&self, format: GraphFormat) -> Result {
+        match format {
+            // Keep to a single line among the events
+            GraphFormat::Json if get_json_messages() => {
+                TERM_OUT.write_line(&serde_json::to_string(self)?)?
+            }
+            GraphFormat::Json => TERM_OUT.write_line(&serde_json::to_string_pretty(self)?)?,
+            GraphFormat::Dot => term_out().write_str(&self.dot())?,
+            GraphFormat::Mermaid => term_out().write_str(&self.mermaid())?,
+        }
+
+        Ok(())
+    }
+
+    /// Groups in the order they first appear, along with their crates
+    fn groups(&self) -> Vec<(&GroupName, Vec<&Node>)> {
+        let mut groups: Vec<(&GroupName, Vec<&Node>)> = vec![];
+
+        for node in &self.nodes {
+            match groups.iter_mut().find(|(g, _)| *g == &node.group) {
+                Some((_, nodes)) => nodes.push(node),
+                None => groups.push((&node.group, vec![node])),
+            }
+        }
+
+        groups
+    }
+
+    fn dot(&self) -> String {
+        let mut out = String::from("digraph workspace {\n");
+
+        for (group_name, nodes) in self.groups() {
+            let _ = writeln!(out, "    subgraph \"cluster_{}\" {{", group_name);
+            let _ = writeln!(out, "        label = \"{}\";", group_name);
+
+            for node in nodes {
+                if node.private {
+                    let _ = writeln!(
+                        out,
+                        "        \"{}\" [label = \"{} (private)\", style = dashed];",
+                        node.name, node.name
+                    );
+                } else {
+                    let _ = writeln!(out, "        \"{}\";", node.name);
+                }
+            }
+
+            out.push_str("    }\n");
+        }
+
+        for edge in &self.edges {
+            let _ = writeln!(
+                out,
+                "    \"{}\" -> \"{}\" [label = \"{}\"];",
+                edge.from,
+                edge.to,
+                edge.kind.as_str()
+            );
+        }
+
+        out.push_str("}\n");
+        out
+    }
+
+    fn mermaid(&self) -> String {
+        // Crate names are not always valid identifiers, so nodes are referred to by position
+        let id = |name: &str| {
+            self.nodes
+                .iter()
+                .position(|n| n.name == name)
+                .map(|i| format!("n{}", i))
+                .unwrap_or_default()
+        };
+
+        let mut out = String::from("flowchart TD\n");
+
+        for (i, (group_name, nodes)) in self.groups().into_iter().enumerate() {
+            let _ = writeln!(out, "    subgraph g{} [\"{}\"]", i, group_name);
+
+            for node in nodes {
+                let _ = writeln!(
+                    out,
+                    "        {}[\"{}\"]{}",
+                    id(&node.name),
+                    node.name,
+                    if node.private { ":::private" } else { "" }
+                );
+            }
+
+            out.push_str("    end\n");
+        }
+
+        for edge in &self.edges {
+            let _ = writeln!(
+                out,
+                "    {} -->|{}| {}",
+                id(&edge.from),
+                edge.kind.as_str(),
+                id(&edge.to)
+            );
+        }
+
+        if self.nodes.iter().any(|n| n.private) {
+            out.push_str("    classDef private stroke-dasharray: 5 5\n");
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn edge(from: &str, to: &str, kind: DepKind) -> Edge {
+        Edge {
+            from: from.to_string(),
+            to: to.to_string(),
+            kind,
+        }
+    }
+
+    #[test]
+    fn test_walk() {
+        let graph = Graph {
+            nodes: vec![],
+            edges: vec![
+                edge("b", "a", DepKind::Normal),
+                edge("c", "b", DepKind::Normal),
+                edge("d", "c", DepKind::Dev),
+                edge("e", "a", DepKind::Build),
+            ],
+        };
+
+        assert_eq!(graph.dependents("a", &[], false), Set::from(["b", "e"]));
+        assert_eq!(
+            graph.dependents("a", &[], true),
+            Set::from(["b", "c", "d", "e"])
+        );
+        assert_eq!(
+            graph.dependents("a", &[DepKind::Normal], true),
+            Set::from(["b", "c"])
+        );
+        assert_eq!(graph.dependencies("d", &[], false), Set::from(["c"]));
+        assert_eq!(
+            graph.dependencies("d", &[], true),
+            Set::from(["a", "b", "c"])
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use crate::utils::{get_dry_run, INTERNAL_ERR};
+
+use lazy_static::lazy_static;
+
+use std::{
+    collections::BTreeMap as Map,
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+enum Journal {
+    Idle,
+    // Original contents of the files changed since recording began, `None` if they did not exist
+    Recording(Map<PathBuf, Option<Vec<u8>>>),
+    RolledBack,
+}
+
+lazy_static! {
+    static ref JOURNAL: Mutex<Journal> = Mutex::new(Journal::Idle);
+}
+
+/// Starts recording the original contents of the files that are changed
+pub fn begin_journal() {
+    if !get_dry_run() {
+        *JOURNAL.lock().expect(INTERNAL_ERR) = Journal::Recording(Map::new());
+    }
+}
+
+/// Stops recording and keeps the changes
+pub fn end_journal() {
+    let mut journal = JOURNAL.lock().expect(INTERNAL_ERR);
+
+    if let Journal::Recording(_) = *journal {
+        *journal = Journal::Idle;
+    }
+}
+
+fn record(journal: &mut Journal, path: &Path) -> io::Result<()> {
+    match journal {
+        Journal::Idle => {}
+        Journal::Recording(files) => {
+            if !files.contains_key(path) {
+                let contents = match fs::read(path) {
+                    Ok(contents) => Some(contents),
+                    Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+                    Err(err) => return Err(err),
+                };
+
+                files.insert(path.to_path_buf(), contents);
+            }
+        }
+        Journal::RolledBack => {
+            return Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "changes were rolled back",
+            ))
+        }
+    }
+
+    Ok(())
+}
+
+/// Records the original contents of a file that is about to be changed by someone else
+pub fn journal_file<P: AsRef<Path>>(path: P) -> io::Result<()> {
+    record(&mut JOURNAL.lock().expect(INTERNAL_ERR), path.as_ref())
+}
+
+/// Runs `change` on the file after recording its original contents. The journal
+/// is held meanwhile so that a rollback never interleaves with the change.
+pub fn journaled<P, F>(path: P, change: F) -> io::Result<()>
+where
+    P: AsRef<Path>,
+    F: FnOnce(&Path) -> io::Result<()>,
+{
+    let mut journal = JOURNAL.lock().expect(INTERNAL_ERR);
+
+    record(&mut journal, path.as_ref())?;
+    change(path.as_ref())
+}
+
+/// Restores the recorded files, returning them or `None` if nothing was being
+/// recorded. No file can be changed through the journal afterwards.
+pub fn rollback_journal() -> io::Result<Option<Vec<PathBuf>>> {
+    let mut journal = JOURNAL.lock().expect(INTERNAL_ERR);
+
+    let files = match std::mem::replace(&mut *journal, Journal::RolledBack) {
+        Journal::Recording(files) => files,
+        other => {
+            *journal = other;
+            return Ok(None);
+        }
+    };
+
+    let mut restored = vec![];
+
+    for (path, contents) in files {
+        match contents {
+            Some(contents) => fs::write(&path, contents)?,
+            None if path.exists() => fs::remove_file(&path)?,
+            None => continue,
+        }
+
+        restored.push(path);
+    }
+
+    Ok(Some(restored))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_rollback() {
+        let dir = std::env::temp_dir().join(format!("cargo-ws-journal-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let existing = dir.join("existing");
+        let created = dir.join("created");
+        fs::write(&existing, "before").unwrap();
+
+        begin_journal();
+
+        journaled(&existing, |path| fs::write(path, "after")).unwrap();
+        journaled(&existing, |path| fs::write(path, "after again")).unwrap();
+        journaled(&created, |path| fs::write(path, "new")).unwrap();
+
+        let restored = rollback_journal().unwrap().unwrap();
+
+        assert_eq!(restored.len(), 2);
+        assert_eq!(fs::read_to_string(&existing).unwrap(), "before");
+        assert!(!created.exists());
+
+        assert!(journaled(&existing, |path| fs::write(path, "after")).is_err());
+        assert_eq!(rollback_journal().unwrap(), None);
+
+        *JOURNAL.lock().unwrap() = Journal::Idle;
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,4 +1,4 @@
-use crate::utils::{GroupName, Result};
+use crate::utils::{get_json_messages, GroupName, Result};
 
 use clap::Parser;
 use oclif::term::TERM_OUT;
@@ -6,7 +6,13 @@ use serde::Serialize;
 
 pub trait Listable: Serialize {
     fn json(&self) -> Result {
-        TERM_OUT.write_line(&serde_json::to_string_pretty(self)?)?;
+        // Keep to a single line among the events
+        if get_json_messages() {
+            TERM_OUT.write_line(&serde_json::to_string(self)?)?;
+        } else {
+            TERM_OUT.write_line(&serde_json::to_string_pretty(self)?)?;
+        }
+
         Ok(())
     }
 
@@ -0,0 +1,285 @@
+use crate::utils::{get_dry_run, index_path, Result};
+
+use camino::Utf8Path;
+use cargo_metadata::{DependencyKind, Package};
+use flate2::read::GzDecoder;
+use semver::{Version, VersionReq};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tar::Archive;
+
+use std::{
+    collections::BTreeMap as Map,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+/// Registry in a local directory, laid out like the `local-registry` sources of cargo
+pub struct LocalRegistry {
+    root: PathBuf,
+    // Unpacked crates, used to verify the crates depending on them
+    sources: PathBuf,
+}
+
+#[derive(Serialize)]
+struct IndexEntry<'a> {
+    name: &'a str,
+    vers: String,
+    deps: Vec<IndexDependency<'a>>,
+    cksum: String,
+    features: Map<&'a str, &'a [String]>,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    features2: Map<&'a str, &'a [String]>,
+    yanked: bool,
+    links: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    v: Option<u32>,
+}
+
+#[derive(Serialize)]
+struct IndexDependency<'a> {
+    name: &'a str,
+    req: String,
+    features: &'a [String],
+    optional: bool,
+    default_features: bool,
+    target: Option<String>,
+    kind: &'static str,
+    registry: Option<&'a str>,
+    package: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct IndexVersion {
+    vers: Version,
+}
+
+impl LocalRegistry {
+    pub fn new(root: &Path, target_dir: &Utf8Path) -> Result<Self> {
+        let registry = Self {
+            root: root.to_path_buf(),
+            sources: target_dir
+                .join("cargo-workspaces-local-registry")
+                .into_std_path_buf(),
+        };
+
+        if !get_dry_run() {
+            fs::create_dir_all(registry.root.join("index"))?;
+        }
+
+        Ok(registry)
+    }
+
+    /// Empty registry in the target directory, used to verify crates before publishing them
+    pub fn overlay(target_dir: &Utf8Path) -> Result<Self> {
+        let root = target_dir
+            .join("cargo-workspaces-overlay")
+            .into_std_path_buf();
+
+        let registry = Self {
+            sources: root.join("sources"),
+            root,
+        };
+
+        // Leftovers of a previous run are discarded even in a dry run, since they are build output
+        if registry.root.exists() {
+            fs::remove_dir_all(&registry.root)?;
+        }
+
+        if !get_dry_run() {
+            fs::create_dir_all(registry.root.join("index"))?;
+        }
+
+        Ok(registry)
+    }
+
+    /// Versions of the crate in the registry
+    fn versions(&self, name: &str) -> Result<Vec<Version>> {
+        let content = match fs::read_to_string(self.index_file(name)) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(err.into()),
+        };
+
+        content
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| Ok(serde_json::from_str::<IndexVersion>(line)?.vers))
+            .collect()
+    }
+
+    pub fn is_published(&self, name: &str, version: &Version) -> Result<bool> {
+        Ok(self.versions(name)?.contains(version))
+    }
+
+    /// Config overrides to resolve the workspace dependencies of the crate from the registry,
+    /// preferring the versions being released over the ones required by the manifest
+    pub fn patches(&self, pkg: &Package, released: &Map<String, Version>) -> Result<Vec<String>> {
+        let mut patches = Map::new();
+
+        for dep in pkg.dependencies.iter().filter(|d| d.path.is_some()) {
+            let versions = self.versions(&dep.name)?;
+
+            let version = match released.get(&dep.name) {
+                Some(version) if versions.contains(version) => Some(version.clone()),
+                _ => versions.into_iter().filter(|v| dep.req.matches(v)).max(),
+            };
+
+            if let Some(version) = version {
+                let source = self.unpack(&dep.name, &version)?;
+
+                patches.insert(
+                    dep.name.clone(),
+                    format!("patch.crates-io.{}.path='{}'", dep.name, source.display()),
+                );
+            }
+        }
+
+        Ok(patches.into_values().collect())
+    }
+
+    /// Adds the packaged crate to the registry, replacing the same version if it exists
+    pub fn add(&self, pkg: &Package, version: &Version, crate_file: &Path) -> Result {
+        let contents = fs::read(crate_file)?;
+        fs::write(self.crate_file(&pkg.name, version), &contents)?;
+
+        let entry = serde_json::to_string(&index_entry(
+            pkg,
+            version,
+            hex::encode(Sha256::digest(&contents)),
+        ))?;
+
+        let index_file = self.index_file(&pkg.name);
+        let mut lines = match fs::read_to_string(&index_file) {
+            Ok(content) => content
+                .lines()
+                .filter(|line| {
+                    serde_json::from_str::<IndexVersion>(line).map_or(true, |x| &x.vers != version)
+                })
+                .map(String::from)
+                .collect(),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => vec![],
+            Err(err) => return Err(err.into()),
+        };
+
+        lines.push(entry);
+
+        fs::create_dir_all(index_file.parent().expect("index file has a parent"))?;
+        fs::write(index_file, lines.join("\n") + "\n")?;
+
+        // Unpack again when needed, in case the crate was packaged before
+        let source = self.sources.join(format!("{}-{}", pkg.name, version));
+        if source.exists() {
+            fs::remove_dir_all(source)?;
+        }
+
+        Ok(())
+    }
+
+    fn unpack(&self, name: &str, version: &Version) -> Result<PathBuf> {
+        let source = self.sources.join(format!("{}-{}", name, version));
+
+        if !source.exists() {
+            let file = fs::File::open(self.crate_file(name, version))?;
+            Archive::new(GzDecoder::new(file)).unpack(&self.sources)?;
+        }
+
+        Ok(source)
+    }
+
+    fn crate_file(&self, name: &str, version: &Version) -> PathBuf {
+        self.root.join(format!("{}-{}.crate", name, version))
+    }
+
+    fn index_file(&self, name: &str) -> PathBuf {
+        self.root.join("index").join(index_path(name))
+    }
+}
+
+fn index_entry<'a>(pkg: &'a Package, version: &Version, cksum: String) -> IndexEntry<'a> {
+    let deps = pkg
+        .dependencies
+        .iter()
+        // `cargo package` removes the dev-dependencies that only have a path
+        .filter(|d| {
+            !(d.kind == DependencyKind::Development
+                && d.path.is_some()
+                && d.req == VersionReq::any())
+        })
+        .map(|d| IndexDependency {
+            name: d.rename.as_deref().unwrap_or(&d.name),
+            req: d.req.to_string(),
+            features: &d.features,
+            optional: d.optional,
+            default_features: d.uses_default_features,
+            target: d.target.as_ref().map(|x| x.to_string()),
+            kind: match d.kind {
+                DependencyKind::Development => "dev",
+                DependencyKind::Build => "build",
+                _ => "normal",
+            },
+            registry: d.registry.as_deref(),
+            package: d.rename.as_ref().map(|_| d.name.as_str()),
+        })
+        .collect();
+
+    // Features using the newer syntax are kept apart so that older versions of cargo can
+    // still read the entry
+    let (features2, features) = pkg
+        .features
+        .iter()
+        .partition::<Map<_, _>, _>(|(_, values)| {
+            values
+                .iter()
+                .any(|x| x.starts_with("dep:") || x.contains("?/"))
+        });
+
+    let features = features
+        .into_iter()
+        .map(|(k, v)| (k.as_str(), v.as_slice()))
+        .collect::<Map<_, _>>();
+
+    let features2 = features2
+        .into_iter()
+        .map(|(k, v)| (k.as_str(), v.as_slice()))
+        .collect::<Map<_, _>>();
+
+    IndexEntry {
+        name: &pkg.name,
+        vers: version.to_string(),
+        deps,
+        cksum,
+        features,
+        v: if features2.is_empty() { None } else { Some(2) },
+        features2,
+        yanked: false,
+        links: pkg.links.as_deref(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_is_published() {
+        let dir = std::env::temp_dir().join(format!("cargo-ws-registry-{}", std::process::id()));
+        let registry = LocalRegistry::new(&dir, Utf8Path::new("target")).unwrap();
+
+        fs::create_dir_all(dir.join("index/de/p1")).unwrap();
+        fs::write(
+            dir.join("index/de/p1/dep1"),
+            "{\"name\":\"dep1\",\"vers\":\"0.1.0\"}\n{\"name\":\"dep1\",\"vers\":\"0.2.0\"}\n",
+        )
+        .unwrap();
+
+        let version = |v| Version::parse(v).unwrap();
+
+        assert!(registry.is_published("dep1", &version("0.1.0")).unwrap());
+        assert!(registry.is_published("dep1", &version("0.2.0")).unwrap());
+        assert!(!registry.is_published("dep1", &version("0.3.0")).unwrap());
+        assert!(!registry.is_published("dep2", &version("0.1.0")).unwrap());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
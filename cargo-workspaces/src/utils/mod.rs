@@ -1,31 +1,65 @@
 mod cargo;
 mod changable;
+mod changelog;
+mod changeset;
 mod config;
+mod conventional;
 mod dag;
+mod dry_run;
 mod error;
+mod event;
+mod exec;
+mod filter;
 mod git;
+mod graph;
+mod journal;
 mod listable;
+mod local_registry;
 mod pkg;
+mod publish_state;
 mod version;
 
 pub use cargo::{
-    cargo, cargo_config_get, change_versions, check_index, is_published, is_unversioned,
-    rename_packages, ManifestDiscriminant,
+    cargo, cargo_config_get, cargo_quiet, change_versions, check_index, index_path, is_published,
+    is_unversioned, package_files, rename_packages, ManifestDiscriminant, RegistryIndex,
 };
-pub use changable::{ChangeData, ChangeOpt};
+pub use changable::{affected_pkgs, Baselines, ChangeOpt, ChangeReason, ChangedPkg, GroupedPkgs};
+pub use changelog::{
+    changelog_entries, package_section, workspace_section, write_changelog, CHANGELOG,
+};
+pub use changeset::{read_changesets, write_changeset, Changeset};
 pub use config::{read_config, PackageConfig, WorkspaceConfig};
+pub use conventional::{commit_messages, recommend_level};
 pub use dag::dag;
+pub use dry_run::{
+    create_file, dry_run_summary, get_dry_run, read_file, remove_file, set_dry_run, would_run,
+    write_file,
+};
 pub(crate) use error::{debug, info};
 pub use error::{get_debug, set_debug, Error};
-pub use git::{git, GitOpt};
+pub use event::{get_json_messages, set_json_messages, term_out, Event, MessageFormat};
+pub use exec::ExecOpt;
+pub use filter::{glob_set, FilterOpt};
+pub use git::{git, GitOpt, TagPrefixes};
+pub use graph::{related_pkgs, DepKind, Graph, GraphFormat};
+pub use journal::{begin_journal, end_journal, journal_file, journaled, rollback_journal};
 pub use listable::{ListOpt, Listable};
-pub use pkg::{get_group_packages, GroupName, Pkg, WorkspaceGroups};
-pub use version::VersionOpt;
+pub use local_registry::LocalRegistry;
+pub use pkg::{get_group_packages, list_pkgs, GroupName, Pkg};
+pub use publish_state::{CrateState, PublishState, PublishStatus, Step};
+pub use version::{ReleaseLevel, VersionOpt};
 
 pub type Result<T = ()> = std::result::Result<T, Error>;
 
 pub const INTERNAL_ERR: &str = "Internal error message. Please create an issue on https://github.com/pksunkara/cargo-workspaces";
 
+pub fn validate_jobs(value: &str) -> std::result::Result<(), String> {
+    match value.parse::<usize>() {
+        Ok(jobs) if jobs > 0 => Ok(()),
+        _ => Err("must be a positive number\n".to_string()),
+    }
+}
+
 pub fn validate_value_containing_name(value: &str) -> std::result::Result<(), String> {
     if !value.contains("%n") {
         return Err("must contain '%n'\n".to_string());
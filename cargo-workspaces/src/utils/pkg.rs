@@ -1,5 +1,6 @@
 use crate::utils::{
-    read_config, Error, ListOpt, Listable, PackageConfig, Result, WorkspaceConfig, INTERNAL_ERR,
+    get_json_messages, read_config, Error, ListOpt, Listable, PackageConfig, Result,
+    WorkspaceConfig, INTERNAL_ERR,
 };
 
 use camino::Utf8PathBuf;
@@ -35,65 +36,76 @@ pub struct Pkg {
 
 impl Listable for Vec<(GroupName, Pkg)> {
     fn list(&self, list: ListOpt) -> Result {
-        if list.json {
+        if list.json || get_json_messages() {
             return self.json();
         }
 
-        if self.is_empty() {
-            return Ok(());
-        }
+        list_pkgs(self.iter().map(|(g, p)| (g, p, None)).collect(), &list)
+    }
+}
 
-        let (first, second, third) =
-            self.iter()
-                .fold((0, 0, 0), |(first, second, third), (_, x)| {
-                    (
-                        max(first, x.name.len()),
-                        max(second, x.version.to_string().len() + 1),
-                        max(third, max(1, x.path.as_os_str().len())),
-                    )
-                });
-
-        let mut last_group_name = None;
-        for (group_name, pkg) in self {
-            match last_group_name.replace(group_name) {
-                Some(prev_name) if group_name == prev_name => {}
-                _ => {
-                    if let Some(group_name) = group_name.pretty_fmt() {
-                        TERM_OUT.write_line(&group_name.to_string())?;
-                    }
+/// Prints the crates in columns, each with an optional note at the end
+pub fn list_pkgs(pkgs: Vec<(&GroupName, &Pkg, Option<String>)>, list: &ListOpt) -> Result {
+    if pkgs.is_empty() {
+        return Ok(());
+    }
+
+    let (first, second, third) =
+        pkgs.iter()
+            .fold((0, 0, 0), |(first, second, third), (_, x, _)| {
+                (
+                    max(first, x.name.len()),
+                    max(second, x.version.to_string().len() + 1),
+                    max(third, max(1, x.path.as_os_str().len())),
+                )
+            });
+
+    let mut last_group_name = None;
+    for (group_name, pkg, note) in pkgs {
+        match last_group_name.replace(group_name) {
+            Some(prev_name) if group_name == prev_name => {}
+            _ => {
+                if let Some(group_name) = group_name.pretty_fmt() {
+                    TERM_OUT.write_line(&group_name.to_string())?;
                 }
             }
-            TERM_OUT.write_str(&pkg.name)?;
-            let mut width = first - pkg.name.len();
-
-            if list.long {
-                TERM_OUT.write_str(&format!(
-                    "{:f$} {}{:s$} {}",
-                    "",
-                    style(format!("v{}", pkg.version)).green(),
-                    "",
-                    style(pkg.path.display()).black().bright(),
-                    f = width,
-                    s = second - pkg.version.to_string().len() - 1,
-                ))?;
-
-                width = third - pkg.path.as_os_str().len();
-            }
+        }
+        TERM_OUT.write_str(&pkg.name)?;
+        let mut width = first - pkg.name.len();
+
+        if list.long {
+            TERM_OUT.write_str(&format!(
+                "{:f$} {}{:s$} {}",
+                "",
+                style(format!("v{}", pkg.version)).green(),
+                "",
+                style(pkg.path.display()).black().bright(),
+                f = width,
+                s = second - pkg.version.to_string().len() - 1,
+            ))?;
+
+            width = third - pkg.path.as_os_str().len();
+        }
 
-            if list.all && pkg.private {
-                TERM_OUT.write_str(&format!(
-                    "{:w$} ({})",
-                    "",
-                    style("PRIVATE").red(),
-                    w = width
-                ))?;
-            }
+        if list.all && pkg.private {
+            TERM_OUT.write_str(&format!(
+                "{:w$} ({})",
+                "",
+                style("PRIVATE").red(),
+                w = width
+            ))?;
 
-            TERM_OUT.write_line("")?;
+            width = 0;
         }
 
-        Ok(())
+        if let Some(note) = note {
+            TERM_OUT.write_str(&format!("{:w$} {}", "", note, w = width))?;
+        }
+
+        TERM_OUT.write_line("")?;
     }
+
+    Ok(())
 }
 
 macro_rules! ser_unit_variant {
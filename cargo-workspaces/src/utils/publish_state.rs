@@ -0,0 +1,200 @@
+use crate::utils::{get_dry_run, Result};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use cargo_metadata::Metadata;
+use semver::Version;
+use serde::{Deserialize, Serialize};
+
+use std::{collections::BTreeMap as Map, fs, io};
+
+const STATE_FILE: &str = "cargo-workspaces-publish.json";
+
+/// Progress of a release, kept until every crate is published so that it can be resumed
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PublishState {
+    /// Common version of the workspace, if a global tag is to be created
+    pub version: Option<Version>,
+    /// New versions of all the crates that were versioned
+    pub versions: Map<String, Version>,
+    /// Last step of the whole release that is complete
+    #[serde(default)]
+    pub step: Step,
+    /// Global tag of the release, once created
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// Crates to publish, in dependency order
+    pub crates: Vec<CrateState>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CrateState {
+    pub name: String,
+    pub version: Version,
+    pub manifest_path: Utf8PathBuf,
+    pub status: PublishStatus,
+    /// Individual tag of the crate, once created
+    #[serde(default)]
+    pub tag: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PublishStatus {
+    Pending,
+    Published,
+    Tagged,
+}
+
+/// Steps of the whole release, in the order they are done
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Step {
+    #[default]
+    Versioned,
+    Committed,
+    Published,
+    Tagged,
+    Pushed,
+}
+
+impl PublishState {
+    /// Location of the state file in the target directory of the workspace
+    pub fn path(metadata: &Metadata) -> Utf8PathBuf {
+        metadata.target_directory.join(STATE_FILE)
+    }
+
+    /// Reads the state of an unfinished release, if there is one
+    pub fn read(path: &Utf8Path) -> Result<Option<Self>> {
+        match fs::read_to_string(path) {
+            Ok(content) => Ok(Some(serde_json::from_str(&content)?)),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save(&self, path: &Utf8Path) -> Result {
+        if get_dry_run() {
+            return Ok(());
+        }
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Tags of the release, including the ones created before resuming
+    pub fn tags(&self) -> Vec<String> {
+        self.crates
+            .iter()
+            .filter_map(|c| c.tag.clone())
+            .chain(self.tag.clone())
+            .collect()
+    }
+
+    /// Forgets the tags that do not exist anymore, which were rolled back after a failure, so
+    /// that they are created again
+    pub fn forget_missing_tags(&mut self, existing: &[&str]) {
+        let missing =
+            |tag: &Option<String>| matches!(tag, Some(tag) if !existing.contains(&tag.as_str()));
+
+        for c in &mut self.crates {
+            if c.status == PublishStatus::Tagged && missing(&c.tag) {
+                c.status = PublishStatus::Published;
+                c.tag = None;
+            }
+        }
+
+        if self.step >= Step::Tagged && missing(&self.tag) {
+            self.step = Step::Published;
+            self.tag = None;
+        }
+    }
+
+    /// Removes the state file once the release is complete
+    pub fn remove(path: &Utf8Path) -> Result {
+        if get_dry_run() {
+            return Ok(());
+        }
+
+        match fs::remove_file(path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let dir = std::env::temp_dir().join(format!("cargo-ws-state-{}", std::process::id()));
+        let path = Utf8PathBuf::from_path_buf(dir.join(STATE_FILE)).unwrap();
+
+        assert_eq!(PublishState::read(&path).unwrap(), None);
+
+        let state = PublishState {
+            version: Some(Version::parse("1.0.0").unwrap()),
+            versions: vec![("dep1".to_string(), Version::parse("1.0.0").unwrap())]
+                .into_iter()
+                .collect(),
+            step: Step::Published,
+            tag: None,
+            crates: vec![CrateState {
+                name: "dep1".to_string(),
+                version: Version::parse("1.0.0").unwrap(),
+                manifest_path: "dep1/Cargo.toml".into(),
+                status: PublishStatus::Tagged,
+                tag: Some("dep1@1.0.0".to_string()),
+            }],
+        };
+
+        state.save(&path).unwrap();
+        assert_eq!(PublishState::read(&path).unwrap(), Some(state));
+
+        PublishState::remove(&path).unwrap();
+        assert_eq!(PublishState::read(&path).unwrap(), None);
+
+        fs::remove_dir(dir).unwrap();
+    }
+
+    #[test]
+    fn test_forget_missing_tags() {
+        let crate_state = |name: &str, status, tag: Option<&str>| CrateState {
+            name: name.to_string(),
+            version: Version::parse("1.0.0").unwrap(),
+            manifest_path: format!("{}/Cargo.toml", name).into(),
+            status,
+            tag: tag.map(String::from),
+        };
+
+        let mut state = PublishState {
+            version: Some(Version::parse("1.0.0").unwrap()),
+            versions: Map::new(),
+            step: Step::Tagged,
+            tag: Some("v1.0.0".to_string()),
+            crates: vec![
+                crate_state("dep1", PublishStatus::Tagged, Some("dep1@1.0.0")),
+                crate_state("dep2", PublishStatus::Tagged, Some("dep2@1.0.0")),
+                crate_state("dep3", PublishStatus::Tagged, None),
+            ],
+        };
+
+        state.forget_missing_tags(&["dep1@1.0.0"]);
+
+        assert_eq!(state.step, Step::Published);
+        assert_eq!(state.tags(), vec!["dep1@1.0.0".to_string()]);
+        assert_eq!(
+            state.crates.iter().map(|c| c.status).collect::<Vec<_>>(),
+            vec![
+                PublishStatus::Tagged,
+                PublishStatus::Published,
+                PublishStatus::Tagged
+            ]
+        );
+    }
+}
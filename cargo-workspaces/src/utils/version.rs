@@ -1,20 +1,21 @@
 use crate::utils::{
-    cargo, change_versions, info, is_unversioned, ChangeData, ChangeOpt, Error, GitOpt, GroupName,
-    ManifestDiscriminant, Pkg, Result, WorkspaceConfig, INTERNAL_ERR,
+    begin_journal, cargo, change_versions, changelog_entries, end_journal, get_group_packages,
+    info, is_unversioned, journal_file, package_section, read_changesets, read_file,
+    recommend_level, remove_file, rollback_journal, term_out, workspace_section, write_changelog,
+    write_file, Baselines, ChangeOpt, Changeset, Error, Event, GitOpt, GroupName, GroupedPkgs,
+    ManifestDiscriminant, Pkg, Result, WorkspaceConfig, CHANGELOG, INTERNAL_ERR,
 };
 
 use cargo_metadata::Metadata;
 use clap::{ArgEnum, Parser};
 use dialoguer::{theme::ColorfulTheme, Confirm, Input, Select};
-use oclif::{
-    console::style,
-    term::{TERM_ERR, TERM_OUT},
-};
+use oclif::{console::style, term::TERM_ERR};
 use semver::{Identifier, Version, VersionReq};
 
 use std::{
     collections::{BTreeMap as Map, HashMap, HashSet},
-    fs,
+    fmt,
+    path::PathBuf,
     process::exit,
 };
 
@@ -30,6 +31,34 @@ pub enum Bump {
     Custom,
 }
 
+/// Level of a release, ordered by severity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, ArgEnum)]
+pub enum ReleaseLevel {
+    Patch,
+    Minor,
+    Major,
+}
+
+impl ReleaseLevel {
+    /// Translate into a version bump, treating breaking changes on `0.x`
+    /// versions as minor bumps as per cargo's semver rules
+    pub fn bump(self, cur_version: &Version) -> Bump {
+        match self {
+            ReleaseLevel::Major if cur_version.major == 0 => Bump::Minor,
+            ReleaseLevel::Major => Bump::Major,
+            ReleaseLevel::Minor => Bump::Minor,
+            ReleaseLevel::Patch => Bump::Patch,
+        }
+    }
+}
+
+impl fmt::Display for ReleaseLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let level = self.to_possible_value().expect(INTERNAL_ERR);
+        write!(f, "{}", level.get_name())
+    }
+}
+
 impl Bump {
     pub fn selected(&self) -> usize {
         match self {
@@ -61,6 +90,17 @@ pub struct VersionOpt {
     #[clap(long, value_name = "identifier", forbid_empty_values(true))]
     pub pre_id: Option<String>,
 
+    /// Infer the version bump of each crate from the conventional commits touching it
+    #[clap(long, conflicts_with_all = &["bump", "no-git"])]
+    pub conventional_commits: bool,
+
+    /// Release the crates declared in the changesets instead of the changed ones
+    #[clap(
+        long,
+        conflicts_with_all = &["bump", "conventional-commits", "force", "ignore-changes"]
+    )]
+    pub from_changesets: bool,
+
     #[clap(flatten)]
     pub change: ChangeOpt,
 
@@ -91,6 +131,14 @@ pub struct VersionOpt {
     /// Do not use a pager for previewing package groups in interactive mode
     #[clap(long)]
     pub no_pager: bool,
+
+    /// Prepend the release notes of each crate to its CHANGELOG.md
+    #[clap(long, conflicts_with = "no-git")]
+    pub changelog: bool,
+
+    /// Also prepend the release notes of all crates, by group, to the workspace CHANGELOG.md
+    #[clap(long, requires = "changelog")]
+    pub workspace_changelog: bool,
 }
 
 impl VersionOpt {
@@ -105,36 +153,60 @@ impl VersionOpt {
             info!("tagging the current commit", "");
         }
 
-        let last_tag = if !self.git.no_git {
-            let change_data = ChangeData::new(metadata, &self.change)?;
+        let prefixes = self.git.tag_prefixes(config)?;
+
+        // Each crate is looked at since its own last release
+        let baselines = if !self.git.no_git {
+            self.change
+                .baselines(metadata, config, &prefixes, self.all)?
+        } else {
+            Baselines::default()
+        };
 
-            if self.change.force.is_none() && change_data.count == "0" && !change_data.dirty {
-                TERM_OUT.write_line("Current HEAD is already released, skipping versioning")?;
+        let changesets = if self.from_changesets {
+            let changesets = read_changesets(&metadata.workspace_root)?;
+
+            if changesets.is_empty() {
+                term_out().write_line("No changesets found, skipping versioning")?;
                 return Ok(None);
             }
 
-            change_data.since
+            changesets
         } else {
-            None
+            vec![]
         };
 
-        let (mut changed_p, mut unchanged_p) = self.change.get_changed_pkgs(
-            metadata,
-            &config,
-            &last_tag,
-            &self.groups[..],
-            self.all,
-        )?;
+        let levels = changesets.iter().fold(Map::new(), |mut levels, (_, x)| {
+            for (name, level) in &x.releases {
+                let cur = levels.entry(name.clone()).or_insert(*level);
+                *cur = (*cur).max(*level);
+            }
+            levels
+        });
+
+        let (mut changed_p, mut unchanged_p) = if self.from_changesets {
+            self.changeset_pkgs(metadata, config, &levels)?
+        } else {
+            let (changed, unchanged, _) = self.change.get_changed_pkgs(
+                metadata,
+                &config,
+                &baselines,
+                &self.groups[..],
+                self.all,
+            )?;
+
+            (changed, unchanged)
+        };
 
         if changed_p.is_empty() {
-            TERM_OUT.write_line("No changes detected, skipping versioning")?;
+            term_out().write_line("No changes detected, skipping versioning")?;
             return Ok(None);
         }
 
         let mut bumped_pkgs = HashMap::new();
 
         while !changed_p.is_empty() {
-            self.get_new_versions(metadata, changed_p, &mut bumped_pkgs)?;
+            self.get_new_versions(metadata, &baselines, &levels, changed_p, &mut bumped_pkgs)?;
 
             let pkgs = unchanged_p.into_iter().partition::<Vec<_>, _>(|(_, p)| {
                 let pkg = metadata
@@ -163,7 +235,7 @@ impl VersionOpt {
         }
 
         if bumped_pkgs.is_empty() {
-            TERM_OUT.write_line(
+            term_out().write_line(
                 "Changes detected but the versions weren't bumped, skipping versioning",
             )?;
             return Ok(None);
@@ -204,8 +276,45 @@ impl VersionOpt {
 
         self.alert_unversioned(unversioned_deps.into_iter().collect())?;
 
+        let pkg_groups = bumped_pkgs
+            .iter()
+            .flat_map(|(group_name, (_, _, pkgs))| {
+                pkgs.iter()
+                    .map(move |(p, _, _)| (p.name.clone(), group_name.clone()))
+            })
+            .collect::<HashMap<_, _>>();
+
         let (new_version, new_versions) = self.confirm_versions(bumped_pkgs)?;
 
+        begin_journal();
+
+        if let Err(err) = self.write_versions(
+            metadata,
+            &baselines,
+            &new_version,
+            &new_versions,
+            &pkg_groups,
+            changesets,
+        ) {
+            self.rollback(metadata)?;
+            return Err(err);
+        }
+
+        end_journal();
+
+        Ok(Some((new_version, new_versions)))
+    }
+
+    /// Writes the new versions to the manifests and commits them
+    fn write_versions(
+        &self,
+        metadata: &Metadata,
+        baselines: &Baselines,
+        new_version: &Option<Version>,
+        new_versions: &Map<String, (Pkg, Version)>,
+        pkg_groups: &HashMap<String, GroupName>,
+        changesets: Vec<(PathBuf, Changeset)>,
+    ) -> Result {
         let mut new_versions_root = Map::new();
 
         let workspace_root = metadata.workspace_root.join("Cargo.toml");
@@ -235,11 +344,11 @@ impl VersionOpt {
             }
 
             let mut new_versions_sub = deps
-                .into_iter()
-                .map(|(key, pkg_name)| {
+                .values()
+                .map(|pkg_name| {
                     (
-                        key,
-                        new_versions.get(&pkg_name).expect(INTERNAL_ERR).1.clone(),
+                        pkg_name.clone(),
+                        new_versions.get(pkg_name).expect(INTERNAL_ERR).1.clone(),
                     )
                 })
                 .collect::<Map<_, _>>();
@@ -255,48 +364,50 @@ impl VersionOpt {
 
             let mut inherited_pkgs = HashSet::new();
 
-            fs::write(
+            write_file(
                 &p.manifest_path,
-                format!(
-                    "{}\n",
-                    change_versions(
-                        fs::read_to_string(&p.manifest_path)?,
-                        &p.name,
-                        &new_versions_sub,
-                        ManifestDiscriminant::Package,
-                        self.exact,
-                        &mut inherited_pkgs,
-                    )?
-                ),
+                change_versions(
+                    read_file(&p.manifest_path)?,
+                    &p.name,
+                    &new_versions_sub,
+                    ManifestDiscriminant::Package,
+                    self.exact,
+                    &mut inherited_pkgs,
+                )?,
             )?;
 
-            new_versions_root.extend(inherited_pkgs.into_iter().filter_map(|pkg_name| {
-                new_versions_sub
-                    .get(&pkg_name)
-                    .map(|version| (pkg_name, version.clone()))
-            }));
+            // Inherited dependencies are reported by their key in the manifest
+            new_versions_root.extend(
+                inherited_pkgs
+                    .iter()
+                    .filter_map(|key| deps.get(key))
+                    .filter_map(|pkg_name| {
+                        new_versions_sub
+                            .get(pkg_name)
+                            .map(|version| (pkg_name.clone(), version.clone()))
+                    }),
+            );
         }
 
-        if let Some(version) = &new_version {
+        if let Some(version) = new_version {
             new_versions_root.insert(workspace_key.clone(), version.clone());
         }
 
-        fs::write(
+        write_file(
             &workspace_root,
-            format!(
-                "{}\n",
-                change_versions(
-                    fs::read_to_string(&workspace_root)?,
-                    &workspace_key,
-                    &new_versions_root,
-                    ManifestDiscriminant::Workspace,
-                    self.exact,
-                    &mut HashSet::new(),
-                )?
-            ),
+            change_versions(
+                read_file(&workspace_root)?,
+                &workspace_key,
+                &new_versions_root,
+                ManifestDiscriminant::Workspace,
+                self.exact,
+                &mut HashSet::new(),
+            )?,
         )?;
 
-        for (pkg_name, (p, _)) in &new_versions {
+        journal_file(metadata.workspace_root.join("Cargo.lock"))?;
+
+        for (pkg_name, (p, _)) in new_versions {
             let output = cargo(
                 &metadata.workspace_root,
                 &[
@@ -316,16 +427,97 @@ impl VersionOpt {
             }
         }
 
-        self.git
-            .commit(&metadata.workspace_root, &new_version, &new_versions)?;
+        let changelogs =
+            self.write_changelogs(metadata, baselines, new_version, new_versions, pkg_groups)?;
+
+        // Changesets naming crates left out of this release are kept for a later one
+        let (changesets, kept) = changesets.into_iter().partition::<Vec<_>, _>(|(_, x)| {
+            x.releases
+                .keys()
+                .all(|name| new_versions.contains_key(name))
+        });
+
+        for (path, x) in &kept {
+            let pending = x
+                .releases
+                .keys()
+                .filter(|name| !new_versions.contains_key(*name))
+                .map(String::as_str)
+                .collect::<Vec<_>>();
+
+            info!(
+                "keeping changeset",
+                format!(
+                    "{} for {}",
+                    path.strip_prefix(&metadata.workspace_root)
+                        .unwrap_or(path)
+                        .display(),
+                    pending.join(", ")
+                )
+            );
+        }
 
-        Ok(Some((new_version, new_versions)))
+        for (path, _) in &changesets {
+            remove_file(path)?;
+        }
+
+        let summaries = changesets
+            .into_iter()
+            .map(|(_, x)| x.summary)
+            .filter(|x| !x.is_empty())
+            .collect::<Vec<_>>();
+
+        self.git.commit(
+            &metadata.workspace_root,
+            new_version,
+            new_versions,
+            &changelogs,
+            &summaries,
+        )?;
+
+        Ok(())
+    }
+
+    /// Restores the files changed while versioning
+    fn rollback(&self, metadata: &Metadata) -> Result {
+        let restored = rollback_journal()?.unwrap_or_default();
+
+        if !restored.is_empty() {
+            self.git.unstage(&metadata.workspace_root, &restored)?;
+            info!("rollback", format!("restored {} file(s)", restored.len()));
+        }
+
+        Ok(())
+    }
+
+    /// Splits the workspace crates into the ones released by the changesets and the rest
+    fn changeset_pkgs(
+        &self,
+        metadata: &Metadata,
+        config: &WorkspaceConfig,
+        levels: &Map<String, ReleaseLevel>,
+    ) -> Result<(GroupedPkgs, GroupedPkgs)> {
+        if let Some(name) = levels
+            .keys()
+            .find(|name| !metadata.packages.iter().any(|p| &&p.name == name))
+        {
+            return Err(Error::PackageNotFound { id: name.clone() });
+        }
+
+        Ok(get_group_packages(metadata, config, self.all)?
+            .into_iter()
+            .partition(|((group_name, _), p)| {
+                (self.groups.is_empty() || self.groups.contains(group_name))
+                    && levels.contains_key(&p.name)
+            }))
     }
 
     fn get_new_versions(
         &self,
         metadata: &Metadata,
-        pkgs: Vec<((GroupName, Option<Version>), Pkg)>,
+        baselines: &Baselines,
+        levels: &Map<String, ReleaseLevel>,
+        pkgs: GroupedPkgs,
         bumped_pkgs: &mut HashMap<
             GroupName,
             (
@@ -384,11 +576,19 @@ impl VersionOpt {
                             .expect(INTERNAL_ERR)
                             .clone();
                         if common_version.is_none() {
+                            let bump = self.inferred_bump(
+                                metadata,
+                                baselines,
+                                levels,
+                                &group_version,
+                                &same_pkgs,
+                            )?;
                             let custom_group_version = self.ask_version(
                                 &group_version,
                                 &group_name,
                                 Some(&same_pkgs[..]),
                                 None,
+                                bump,
                             )?;
                             *common_version = Some(group_version);
                             group_version = custom_group_version;
@@ -411,8 +611,15 @@ impl VersionOpt {
 
             for p in independent_pkgs {
                 let old_version = p.version.clone();
+                let bump = self.inferred_bump(
+                    metadata,
+                    baselines,
+                    levels,
+                    &old_version,
+                    std::slice::from_ref(&p),
+                )?;
                 let new_version =
-                    self.ask_version(&old_version, &group_name, None, Some(&p.name))?;
+                    self.ask_version(&old_version, &group_name, None, Some(&p.name), bump)?;
                 if old_version != new_version {
                     new_versions.push((p, new_version, old_version));
                 }
@@ -426,6 +633,84 @@ impl VersionOpt {
         Ok(())
     }
 
+    fn write_changelogs(
+        &self,
+        metadata: &Metadata,
+        baselines: &Baselines,
+        new_version: &Option<Version>,
+        new_versions: &Map<String, (Pkg, Version)>,
+        pkg_groups: &HashMap<String, GroupName>,
+    ) -> Result<Vec<PathBuf>> {
+        let mut changelogs = vec![];
+
+        if !self.changelog {
+            return Ok(changelogs);
+        }
+
+        let mut groups: Vec<(GroupName, Vec<_>)> = vec![];
+
+        for (p, version) in new_versions.values() {
+            let entries =
+                changelog_entries(&metadata.workspace_root, baselines.of(&p.name), &p.path)?;
+
+            // The root crate shares its changelog with the workspace
+            if !(self.workspace_changelog && p.path.as_os_str() == ".") {
+                changelogs.push(write_changelog(
+                    p.location.join(CHANGELOG),
+                    &package_section(version, &entries),
+                )?);
+            }
+
+            let group_name = pkg_groups.get(&p.name).expect(INTERNAL_ERR);
+
+            match groups.iter_mut().find(|(name, _)| name == group_name) {
+                Some((_, pkgs)) => pkgs.push((p, version, entries)),
+                None => groups.push((group_name.clone(), vec![(p, version, entries)])),
+            }
+        }
+
+        if self.workspace_changelog {
+            groups.sort_by_key(|(name, _)| (*name != GroupName::Default, name.to_string()));
+
+            changelogs.push(write_changelog(
+                metadata.workspace_root.join(CHANGELOG).into(),
+                &workspace_section(new_version, &groups),
+            )?);
+        }
+
+        info!("changelog", format!("updated {} file(s)", changelogs.len()));
+
+        Ok(changelogs)
+    }
+
+    /// Highest bump declared by the changesets or recommended by the
+    /// conventional commits for any of `pkgs`
+    fn inferred_bump(
+        &self,
+        metadata: &Metadata,
+        baselines: &Baselines,
+        levels: &Map<String, ReleaseLevel>,
+        cur_version: &Version,
+        pkgs: &[Pkg],
+    ) -> Result<Option<Bump>> {
+        let mut level = None;
+
+        for p in pkgs {
+            let pkg_level = if self.from_changesets {
+                // Dependents of the released crates are not part of the changesets
+                levels.get(&p.name).copied().unwrap_or(ReleaseLevel::Patch)
+            } else if self.conventional_commits {
+                recommend_level(&metadata.workspace_root, baselines.of(&p.name), &p.path)?
+            } else {
+                return Ok(None);
+            };
+
+            level = level.max(Some(pkg_level));
+        }
+
+        Ok(level.map(|x| x.bump(cur_version)))
+    }
+
     fn alert_unversioned(
         &self,
         mut pkgs: Vec<(&str, (&str, Vec<(&str, &VersionReq, &Version)>))>,
@@ -560,6 +845,13 @@ impl VersionOpt {
                 TERM_ERR.write_line("")?;
             }
             for (p, new_version, cur_version) in versions {
+                Event::VersionPlanned {
+                    name: &p.name,
+                    from: &cur_version,
+                    to: &new_version,
+                }
+                .emit()?;
+
                 TERM_ERR.write_line(&format!(
                     " - {}: {} => {}",
                     style(&p.name).yellow().for_stderr(),
@@ -592,6 +884,7 @@ impl VersionOpt {
         group: &GroupName,
         mut group_pkgs: Option<&[Pkg]>,
         pkg_name: Option<&str>,
+        bump: Option<Bump>,
     ) -> Result<Version> {
         let mut items = version_items(cur_version, &self.pre_id);
 
@@ -611,7 +904,7 @@ impl VersionOpt {
 
         let theme = ColorfulTheme::default();
 
-        let selected = if let Some(bump) = &self.bump {
+        let selected = if let Some(bump) = bump.as_ref().or(self.bump.as_ref()) {
             bump.selected()
         } else {
             loop {
@@ -37,6 +37,7 @@ impl Version {
                     &metadata.workspace_root,
                     &new_version,
                     &new_versions,
+                    &config,
                 )? {
                     tags.push(tag)
                 }
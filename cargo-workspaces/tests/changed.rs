@@ -0,0 +1,282 @@
+mod utils;
+use std::fs;
+
+#[test]
+fn test_mixed_tags() {
+    let dir = utils::git_fixture("normal", "changed-mixed");
+
+    utils::touch(&dir, "dep2/src/lib.rs");
+    utils::commit(&dir, "change dep2");
+
+    utils::touch(&dir, "dep1/src/lib.rs");
+    utils::commit(&dir, "change dep1");
+    utils::git(&dir, &["tag", "dep1@0.1.1"]);
+
+    // Released on its own, which says nothing about dep2
+    let (out, _) = utils::run(&dir, &["ws", "changed"]);
+    assert_eq!(out, "dep2\n");
+
+    utils::touch(&dir, "README.md");
+    utils::commit(&dir, "unrelated");
+
+    let (out, _) = utils::run(&dir, &["ws", "changed"]);
+    assert_eq!(out, "dep2\n");
+
+    let (_, err) = utils::run(&dir, &["ws", "version", "patch", "-y", "--no-git-push"]);
+    assert!(err.contains(" - dep2: 0.1.0 => 0.1.1\n"));
+    assert!(utils::git(&dir, &["tag", "-l"]).contains("dep2@0.1.1"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_dirty() {
+    let dir = utils::git_fixture("normal", "changed-dirty");
+
+    utils::touch(&dir, "dep1/src/lib.rs");
+
+    // The working tree is compared by default
+    let (out, _) = utils::run(&dir, &["ws", "changed"]);
+    assert_eq!(out, "dep1\n");
+
+    let (out, _) = utils::run(&dir, &["ws", "changed", "--include-dirty"]);
+    assert_eq!(out, "dep1 (uncommitted)\n");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_ignore_unpackaged() {
+    let dir = utils::git_fixture("normal", "changed-unpackaged");
+    let manifest = format!("{}/dep1/Cargo.toml", dir);
+
+    let content = fs::read_to_string(&manifest).unwrap();
+    let content = content.replace(
+        "[dependencies]",
+        "exclude = [\"tests/*\"]\n\n[dependencies]",
+    );
+    fs::write(&manifest, content).unwrap();
+    utils::commit(&dir, "exclude tests");
+    utils::git(&dir, &["tag", "v0.1.1"]);
+
+    utils::touch(&dir, "dep1/tests/it.rs");
+    utils::commit(&dir, "change dep1 tests");
+
+    let (out, _) = utils::run(&dir, &["ws", "changed"]);
+    assert_eq!(out, "dep1\n");
+
+    let (out, _) = utils::run(&dir, &["ws", "changed", "--ignore-unpackaged"]);
+    assert_eq!(out, "");
+
+    let (out, _) = utils::run(
+        &dir,
+        &[
+            "ws",
+            "version",
+            "patch",
+            "-y",
+            "--no-git-push",
+            "--ignore-unpackaged",
+        ],
+    );
+    assert_eq!(out, "No changes detected, skipping versioning\n");
+    assert_eq!(utils::git(&dir, &["tag", "-l"]), "v0.1.0\nv0.1.1");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_ignore_changes() {
+    let dir = utils::git_fixture("normal", "changed-ignore");
+
+    utils::append(
+        &dir,
+        "dep1/Cargo.toml",
+        "\n[package.metadata.workspaces]\nignore_changes = [\"*.md\"]\n",
+    );
+    utils::commit(&dir, "ignore docs");
+    utils::git(&dir, &["tag", "v0.1.1"]);
+
+    utils::touch(&dir, "dep1/README.md");
+    utils::commit(&dir, "change dep1 docs");
+
+    let (out, _) = utils::run(&dir, &["ws", "changed"]);
+    assert_eq!(out, "");
+
+    let (out, _) = utils::run(&dir, &["ws", "version", "patch", "-y", "--no-git-push"]);
+    assert_eq!(out, "No changes detected, skipping versioning\n");
+    assert_eq!(utils::git(&dir, &["tag", "-l"]), "v0.1.0\nv0.1.1");
+
+    utils::touch(&dir, "dep1/src/lib.rs");
+    utils::commit(&dir, "change dep1");
+
+    let (out, _) = utils::run(&dir, &["ws", "changed"]);
+    assert_eq!(out, "dep1\n");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_config() {
+    let dir = utils::git_fixture("changes", "changed-config");
+
+    // Forced by the workspace and the package config
+    let (out, _) = utils::run(&dir, &["ws", "changed"]);
+    assert_eq!(out, "forced\npinned\n");
+
+    // Ignored by the workspace config, and by the package config for docs
+    utils::touch(&dir, "docs/README.md");
+    utils::touch(&dir, "docs/notes.txt");
+    utils::touch(&dir, "plain/README.md");
+    utils::commit(&dir, "change docs");
+
+    let (out, _) = utils::run(&dir, &["ws", "changed"]);
+    assert_eq!(out, "forced\npinned\n");
+
+    utils::touch(&dir, "plain/notes.txt");
+    utils::commit(&dir, "change plain");
+
+    let (out, _) = utils::run(&dir, &["ws", "changed"]);
+    assert_eq!(out, "forced\npinned\nplain\n");
+
+    // Added to the ones of the config
+    let (out, _) = utils::run(
+        &dir,
+        &[
+            "ws",
+            "changed",
+            "--ignore-changes",
+            "**/*.txt",
+            "--force",
+            "docs",
+        ],
+    );
+    assert_eq!(out, "docs\nforced\npinned\n");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_watch() {
+    let dir = utils::git_fixture("normal", "changed-watch");
+
+    utils::append(
+        &dir,
+        "dep1/Cargo.toml",
+        "\n[package.metadata.workspaces]\nwatch = [\"../proto/**\"]\n",
+    );
+    utils::commit(&dir, "watch proto");
+    utils::git(&dir, &["tag", "v0.1.1"]);
+
+    utils::touch(&dir, "proto/api.proto");
+    utils::commit(&dir, "change proto");
+
+    let (out, _) = utils::run(&dir, &["ws", "changed"]);
+    assert_eq!(out, "dep1\n");
+
+    utils::git(&dir, &["tag", "v0.1.2"]);
+    utils::touch(&dir, "assets/logo.svg");
+    utils::commit(&dir, "change assets");
+
+    let (out, _) = utils::run(&dir, &["ws", "changed"]);
+    assert_eq!(out, "");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Fixture where top only depends on dep1 through dep2
+fn chain_fixture(name: &str) -> String {
+    let dir = utils::git_fixture("normal", name);
+    let manifest = format!("{}/top/Cargo.toml", dir);
+
+    let content = fs::read_to_string(&manifest).unwrap();
+    let content = content
+        .lines()
+        .filter(|x| !x.starts_with("dep = "))
+        .map(|x| format!("{}\n", x))
+        .collect::<String>();
+    fs::write(&manifest, content).unwrap();
+    utils::commit(&dir, "only through dep2");
+    utils::git(&dir, &["tag", "v0.1.1"]);
+
+    dir
+}
+
+#[test]
+fn test_include_dependents() {
+    let dir = chain_fixture("changed-dependents");
+
+    utils::touch(&dir, "dep1/src/lib.rs");
+    utils::commit(&dir, "change dep1");
+
+    let (out, _) = utils::run(&dir, &["ws", "changed"]);
+    assert_eq!(out, "dep1\n");
+
+    let (out, _) = utils::run(&dir, &["ws", "changed", "--include-dependents"]);
+    assert_eq!(out, "dep1\ndep2 (via dep1)\n");
+
+    let (out, _) = utils::run(&dir, &["ws", "changed", "--include-dependents=direct"]);
+    assert_eq!(out, "dep1\ndep2 (via dep1)\n");
+
+    let (out, _) = utils::run(&dir, &["ws", "changed", "--include-dependents=transitive"]);
+    assert_eq!(out, "dep1\ndep2 (via dep1)\ntop  (via dep2)\n");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_include_dependents_output() {
+    let dir = chain_fixture("changed-dependents-output");
+
+    utils::touch(&dir, "dep1/src/lib.rs");
+    utils::commit(&dir, "change dep1");
+
+    let args = ["ws", "changed", "--include-dependents=transitive"];
+
+    let (out, _) = utils::run(&dir, &[&args[..], &["-l"]].concat());
+    assert_eq!(
+        out,
+        "dep1 v0.1.0 dep1\ndep2 v0.1.0 dep2 (via dep1)\ntop  v0.1.0 top  (via dep2)\n"
+    );
+
+    // Each crate is followed by the reason it changed
+    let (out, _) = utils::run(&dir, &[&args[..], &["--json"]].concat());
+    let crates = out.split(r#""name": "#).skip(1).collect::<Vec<_>>();
+    assert_eq!(crates.len(), 3, "{}", out);
+
+    assert!(crates[0].starts_with(r#""dep1""#), "{}", out);
+    assert!(crates[0].contains(r#""reason": "direct""#), "{}", out);
+    assert!(!crates[0].contains(r#""via""#), "{}", out);
+
+    assert!(crates[1].starts_with(r#""dep2""#), "{}", out);
+    assert!(crates[1].contains(r#""reason": "dependency""#), "{}", out);
+    assert!(crates[1].contains(r#""via": "dep1""#), "{}", out);
+
+    assert!(crates[2].starts_with(r#""top""#), "{}", out);
+    assert!(crates[2].contains(r#""reason": "dependency""#), "{}", out);
+    assert!(crates[2].contains(r#""via": "dep2""#), "{}", out);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_include_dev_dependents() {
+    let dir = utils::git_fixture("normal", "changed-dev-dependents");
+
+    // dep1 only uses dep2 in its tests
+    utils::append(
+        &dir,
+        "dep1/Cargo.toml",
+        "\n[dev-dependencies]\ndep2 = { path = \"../dep2\" }\n",
+    );
+    utils::commit(&dir, "test dep1 with dep2");
+    utils::git(&dir, &["tag", "v0.1.1"]);
+
+    utils::touch(&dir, "dep2/src/lib.rs");
+    utils::commit(&dir, "change dep2");
+
+    let (out, _) = utils::run(&dir, &["ws", "changed", "--include-dependents"]);
+    assert_eq!(out, "dep2\ndep1 (via dep2)\ntop  (via dep2)\n");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
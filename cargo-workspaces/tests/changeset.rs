@@ -0,0 +1,75 @@
+mod utils;
+use std::{fs, path::Path};
+
+#[test]
+fn test_partial_release() {
+    let dir = utils::git_fixture("normal", "changeset-partial");
+
+    utils::append(
+        &dir,
+        "Cargo.toml",
+        "\n[[workspace.metadata.workspaces.group]]\nname = \"deps\"\nmembers = [\"dep1\", \"dep2\"]\n",
+    );
+
+    for args in [
+        ["dep1:patch", "Fix dep1", "dep1"],
+        ["top:minor", "Add to top", "top"],
+    ] {
+        let (_, err) = utils::run(
+            &dir,
+            &["ws", "changeset", args[0], "-m", args[1], "--name", args[2]],
+        );
+        assert!(err.contains(&format!("created .changeset/{}.md", args[2])));
+    }
+
+    utils::commit(&dir, "changesets");
+
+    let (_, err) = utils::run(
+        &dir,
+        &[
+            "ws",
+            "version",
+            "--from-changesets",
+            "--groups",
+            "deps",
+            "-y",
+            "--no-git-push",
+        ],
+    );
+    assert!(err.contains("keeping changeset .changeset/top.md for top\n"));
+
+    // The changeset of the crate left out is still there for the next release
+    let changesets = Path::new(&dir).join(".changeset");
+    assert!(!changesets.join("dep1.md").exists());
+    assert!(changesets.join("top.md").exists());
+
+    let message = utils::git(&dir, &["log", "-1", "--format=%B"]);
+    assert!(message.contains("Fix dep1"));
+    assert!(!message.contains("Add to top"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_existing_name() {
+    let dir = utils::git_fixture("normal", "changeset-existing");
+    let args = [
+        "ws",
+        "changeset",
+        "dep1:patch",
+        "-m",
+        "Fix dep1",
+        "--name",
+        "fix",
+    ];
+
+    utils::run(&dir, &args);
+
+    let err = utils::run_err(&dir, &[&args[..4], &["Fix again"], &args[5..]].concat());
+    assert_eq!(err, "error: changeset .changeset/fix.md already exists\n");
+
+    let content = fs::read_to_string(Path::new(&dir).join(".changeset/fix.md")).unwrap();
+    assert!(content.contains("Fix dep1"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,35 @@
+mod utils;
+use insta::assert_snapshot;
+
+#[test]
+fn test_dependents() {
+    let out = utils::run_out("../fixtures/normal", &["ws", "dependents", "dep1"]);
+    assert_snapshot!(out);
+}
+
+#[test]
+fn test_dependents_kind() {
+    let out = utils::run_out(
+        "../fixtures/normal",
+        &["ws", "dependents", "dep1", "--kind", "dev"],
+    );
+    assert!(out.is_empty());
+}
+
+#[test]
+fn test_dependencies() {
+    let out = utils::run_out(
+        "../fixtures/normal",
+        &["ws", "dependencies", "top", "--json"],
+    );
+
+    assert!(out.contains(r#""name": "dep1""#));
+    assert!(out.contains(r#""name": "dep2""#));
+    assert!(!out.contains(r#""name": "top""#));
+}
+
+#[test]
+fn test_not_found() {
+    let err = utils::run_err("../fixtures/normal", &["ws", "dependents", "missing"]);
+    assert_eq!(err, "error: unable to find package missing\n");
+}
@@ -16,3 +16,98 @@ fn test_normal() {
     assert_snapshot!(err);
     assert_snapshot!(out);
 }
+
+#[cfg(not(windows))]
+#[test]
+fn test_jobs() {
+    let (out, err) = utils::run(
+        "../fixtures/normal",
+        &[
+            "ws",
+            "exec",
+            "--jobs",
+            "2",
+            "--no-bail",
+            "sh",
+            "-c",
+            "echo $PWD; false",
+        ],
+    );
+    assert_snapshot!(without_times(&err));
+
+    let mut lines = out
+        .lines()
+        .map(|x| x.split(" | ").next().unwrap())
+        .collect::<Vec<_>>();
+    lines.sort();
+    assert_eq!(lines, vec!["dep1", "dep2", "top "]);
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_filter() {
+    let (out, _) = utils::run(
+        "../fixtures/normal",
+        &[
+            "ws",
+            "exec",
+            "--include",
+            "dep*",
+            "--ignore",
+            "*1",
+            "sh",
+            "-c",
+            "echo $CARGO_WS_PKG_NAME",
+        ],
+    );
+
+    assert_eq!(out, "dep2\n");
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_private() {
+    let args = ["ws", "exec", "sh", "-c", "echo $CARGO_WS_PKG_NAME"];
+
+    let (out, _) = utils::run("../fixtures/private", &args);
+    assert_eq!(out, "private\nsimple\n");
+
+    let (out, _) = utils::run(
+        "../fixtures/private",
+        &[&args[..2], &["--no-private"], &args[2..]].concat(),
+    );
+    assert_eq!(out, "simple\n");
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_env() {
+    let (out, _) = utils::run(
+        "../fixtures/normal",
+        &[
+            "ws",
+            "exec",
+            "sh",
+            "-c",
+            "echo $CARGO_WS_TOPO_INDEX $CARGO_WS_GROUP $CARGO_WS_PKG_NAME $CARGO_WS_PKG_VERSION $CARGO_WS_PKG_PATH $CARGO_WS_PKG_PRIVATE",
+        ],
+    );
+    assert_snapshot!(out);
+}
+
+// Durations in the summary change between runs
+#[cfg(not(windows))]
+fn without_times(err: &str) -> String {
+    err.lines()
+        .map(|line| match line.rsplit_once(' ') {
+            Some((rest, time))
+                if time.ends_with('s') && time[..time.len() - 1].parse::<f64>().is_ok() =>
+            {
+                format!("{} [time]", rest)
+            }
+            _ => line.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        + "\n"
+}
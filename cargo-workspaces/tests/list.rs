@@ -62,3 +62,31 @@ fn test_json_conflicts_with_long() {
     let err = utils::run_err("../fixtures/private", &["ws", "list", "--long", "--json"]);
     assert_snapshot!(err);
 }
+
+#[test]
+fn test_graph_dot() {
+    let out = utils::run_out("../fixtures/normal", &["ws", "list", "--graph", "dot"]);
+    assert_snapshot!(out);
+}
+
+#[test]
+fn test_graph_mermaid() {
+    let out = utils::run_out("../fixtures/private", &["ws", "list", "--graph", "mermaid"]);
+    assert_snapshot!(out);
+}
+
+#[test]
+fn test_graph_dot_private() {
+    let out = utils::run_out("../fixtures/private", &["ws", "list", "--graph", "dot"]);
+    assert_snapshot!(out);
+}
+
+#[test]
+fn test_graph_json() {
+    let out = utils::run_out("../fixtures/normal", &["ws", "list", "--graph", "json"]);
+
+    assert!(out.contains(r#""from": "top""#));
+    assert!(out.contains(r#""to": "dep2""#));
+    assert!(out.contains(r#""kind": "normal""#));
+    assert!(out.contains(r#""group": "default""#));
+}
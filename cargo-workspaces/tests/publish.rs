@@ -0,0 +1,301 @@
+mod utils;
+use std::{fs, process::Command as StdCommand};
+
+#[test]
+fn test_resume() {
+    let dir = utils::git_fixture("normal", "publish-resume");
+
+    // Every crate was published before the release failed, dep1 was tagged but the tag of top
+    // was rolled back
+    utils::git(&dir, &["tag", "dep1@0.2.0"]);
+
+    let crate_state = |name: &str, status: &str, tag: &str| {
+        format!(
+            r#"{{"name": "{name}", "version": "0.2.0", "manifest_path": "{dir}/{name}/Cargo.toml", "status": "{status}", "tag": {tag}}}"#,
+            name = name,
+            dir = dir,
+            status = status,
+            tag = tag,
+        )
+    };
+
+    let state = format!(
+        r#"{{"version": "0.2.0", "versions": {{"dep1": "0.2.0", "dep2": "0.2.0", "top": "0.2.0"}}, "step": "committed", "tag": null, "crates": [{}, {}, {}]}}"#,
+        crate_state("dep1", "tagged", r#""dep1@0.2.0""#),
+        crate_state("dep2", "published", "null"),
+        crate_state("top", "tagged", r#""top@0.2.0""#),
+    );
+
+    fs::create_dir_all(format!("{}/target", dir)).unwrap();
+    fs::write(
+        format!("{}/target/cargo-workspaces-publish.json", dir),
+        state,
+    )
+    .unwrap();
+
+    // The existing tags are still looked up in a dry run
+    let (_, err) = utils::run(
+        &dir,
+        &["ws", "publish", "--resume", "--no-git-push", "--dry-run"],
+    );
+    assert!(err.contains("would run git tag top@0.2.0 "), "{}", err);
+    assert!(!err.contains("would run git tag -l"), "{}", err);
+    assert!(!err.contains("dep1@0.2.0"), "{}", err);
+
+    let (_, err) = utils::run(&dir, &["ws", "publish", "--resume", "--no-git-push"]);
+    assert!(err.contains("0 of 3 crates left to publish"), "{}", err);
+    assert!(err.contains("success ok"), "{}", err);
+
+    // Only the missing tags are created
+    assert!(!err.contains("tagging dep1@0.2.0"));
+    assert_eq!(
+        utils::git(&dir, &["tag", "-l"]),
+        "dep1@0.2.0\ndep2@0.2.0\ntop@0.2.0\nv0.1.0\nv0.2.0"
+    );
+    assert!(fs::metadata(format!("{}/target/cargo-workspaces-publish.json", dir)).is_err());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_jobs() {
+    let dir = utils::git_fixture("normal", "publish-jobs");
+    let registry = format!("{}/target/registry", dir);
+
+    let (out, err) = utils::run(
+        &dir,
+        &[
+            "ws",
+            "--message-format",
+            "json",
+            "publish",
+            "--from-git",
+            "--no-git-push",
+            "--jobs",
+            "2",
+            "--local-registry",
+            &registry,
+        ],
+    );
+    assert!(err.contains("success ok"), "{}", err);
+
+    // Dependents are only started once their dependencies are published
+    let published = out
+        .lines()
+        .filter(|x| x.contains(r#""event":"published""#))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        published,
+        [
+            r#"{"event":"published","name":"dep1","version":"0.1.0"}"#,
+            r#"{"event":"published","name":"dep2","version":"0.1.0"}"#,
+            r#"{"event":"published","name":"top","version":"0.1.0"}"#,
+        ]
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_jobs_failure() {
+    let dir = utils::git_fixture("normal", "publish-jobs-failure");
+    let registry = format!("{}/target/registry", dir);
+
+    // A crate without dependencies that is started first but fails to build
+    utils::append(
+        &dir,
+        "bad/Cargo.toml",
+        "[package]\nname = \"bad\"\nversion = \"0.1.0\"\nedition = \"2018\"\n",
+    );
+    utils::append(&dir, "bad/src/lib.rs", "compile_error!(\"bad\");\n");
+    let manifest = fs::read_to_string(format!("{}/Cargo.toml", dir)).unwrap();
+    fs::write(
+        format!("{}/Cargo.toml", dir),
+        manifest.replace("members = [", "members = [\n\t\"bad\","),
+    )
+    .unwrap();
+    utils::commit(&dir, "bad");
+
+    let (_, err) = utils::run(
+        &dir,
+        &[
+            "ws",
+            "publish",
+            "--from-git",
+            "--no-git-push",
+            "--jobs",
+            "1",
+            "--local-registry",
+            &registry,
+        ],
+    );
+    assert!(err.contains("error: unable to package bad"), "{}", err);
+
+    // No new crates are started after the failure
+    assert!(!err.contains("packaging dep1"), "{}", err);
+    assert!(!err.contains("packaging dep2"), "{}", err);
+    assert!(!err.contains("packaging top"), "{}", err);
+    assert!(err.contains("run `publish --resume` to continue this release"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_preflight() {
+    let dir = utils::git_fixture("normal", "publish-preflight");
+
+    // The last crate to publish fails to build
+    utils::append(&dir, "top/src/main.rs", "compile_error!(\"top\");\n");
+    utils::commit(&dir, "top");
+
+    let (_, err) = utils::run(&dir, &["ws", "publish", "--from-git", "--no-git-push"]);
+    assert!(err.contains("pre-flight packaging 3 crates"), "{}", err);
+    assert!(err.contains("packaged dep2 v0.1.0"), "{}", err);
+    assert!(err.contains("error: unable to package top"), "{}", err);
+
+    // Nothing was uploaded or tagged
+    assert!(!err.contains("publishing"), "{}", err);
+    assert_eq!(utils::git(&dir, &["tag", "-l"]), "v0.1.0");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_no_preflight() {
+    let dir = utils::git_fixture("normal", "publish-no-preflight");
+
+    // The crates go to a registry whose index is served locally
+    utils::append(
+        &dir,
+        ".cargo/config.toml",
+        &format!("[registries.local]\nindex = \"{}\"\n", utils::empty_index()),
+    );
+
+    for name in ["dep1", "dep2", "top"] {
+        let path = format!("{}/{}/Cargo.toml", dir, name);
+        let manifest = fs::read_to_string(&path).unwrap();
+        fs::write(
+            &path,
+            manifest.replace("edition", "publish = [\"local\"]\nedition"),
+        )
+        .unwrap();
+    }
+
+    utils::commit(&dir, "local");
+
+    let args = ["ws", "publish", "--from-git", "--no-git-push", "--dry-run"];
+
+    // Verified crates are uploaded without building them again
+    let (_, err) = utils::run(&dir, &args);
+    assert!(err.contains("would run cargo package"), "{}", err);
+    assert!(
+        err.contains("would run cargo publish --no-verify"),
+        "{}",
+        err
+    );
+
+    let (_, err) = utils::run(&dir, &[&args[..], &["--no-preflight"]].concat());
+    assert!(!err.contains("pre-flight"), "{}", err);
+    assert!(!err.contains("would run cargo package"), "{}", err);
+    assert!(!err.contains("--no-verify"), "{}", err);
+    assert!(
+        err.contains(&format!(
+            "would run cargo publish --manifest-path {}/dep1/Cargo.toml",
+            dir
+        )),
+        "{}",
+        err
+    );
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_local_registry() {
+    let dir = utils::git_fixture("normal", "publish-local-registry");
+    let registry = format!("{}-registry", dir);
+    let consumer = format!("{}-consumer", dir);
+
+    // Without a remote, as nothing is pushed
+    let (_, err) = utils::run(
+        &dir,
+        &[
+            "ws",
+            "publish",
+            "minor",
+            "-y",
+            "--force",
+            "*",
+            "--local-registry",
+            &registry,
+        ],
+    );
+    assert!(err.contains("packaged top v0.2.0"), "{}", err);
+    assert!(err.contains("success ok"), "{}", err);
+
+    // The crates are versioned but not tagged
+    assert!(utils::git(&dir, &["log", "-1", "--format=%s"]).starts_with("Release"));
+    assert_eq!(utils::git(&dir, &["tag", "-l"]), "v0.1.0");
+
+    // A crate outside the workspace can be built against the registry
+    utils::append(
+        &consumer,
+        "Cargo.toml",
+        "[package]\nname = \"consumer\"\nversion = \"0.1.0\"\nedition = \"2018\"\n\n\
+         [dependencies]\ndep2 = \"0.2.0\"\n\n[workspace]\n",
+    );
+    utils::append(&consumer, "src/lib.rs", "pub use dep2;\n");
+
+    let output = StdCommand::new("cargo")
+        .current_dir(&consumer)
+        .args([
+            "build",
+            "--offline",
+            "--config",
+            "source.crates-io.replace-with = \"local\"",
+            "--config",
+            &format!("source.local.local-registry = \"{}\"", registry),
+        ])
+        .output()
+        .unwrap();
+    let err = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success(), "{}", err);
+    assert!(err.contains("dep1 v0.2.0"), "{}", err);
+    assert!(err.contains("dep2 v0.2.0"), "{}", err);
+
+    fs::remove_dir_all(&dir).unwrap();
+    fs::remove_dir_all(&registry).unwrap();
+    fs::remove_dir_all(&consumer).unwrap();
+}
+
+#[test]
+fn test_unfinished() {
+    let dir = utils::git_fixture("normal", "publish-unfinished");
+    let state_path = format!("{}/target/cargo-workspaces-publish.json", dir);
+
+    let state =
+        r#"{"version": null, "versions": {}, "step": "committed", "tag": null, "crates": []}"#;
+    fs::create_dir_all(format!("{}/target", dir)).unwrap();
+    fs::write(&state_path, state).unwrap();
+
+    // The unfinished release is kept instead of starting a new one
+    let (_, err) = utils::run(
+        &dir,
+        &[
+            "ws",
+            "publish",
+            "patch",
+            "-y",
+            "--force",
+            "*",
+            "--no-git-push",
+        ],
+    );
+    assert!(err.contains("unfinished release"), "{}", err);
+    assert!(err.contains("publish --resume"), "{}", err);
+
+    assert_eq!(fs::read_to_string(&state_path).unwrap(), state);
+    assert_eq!(utils::git(&dir, &["log", "--format=%s"]), "init");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
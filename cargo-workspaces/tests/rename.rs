@@ -0,0 +1,54 @@
+mod utils;
+use insta::assert_snapshot;
+use std::fs::{read_to_string, remove_dir_all};
+
+#[test]
+fn test_dry_run() {
+    let manifest = "../fixtures/normal/dep1/Cargo.toml";
+    let before = read_to_string(manifest).unwrap();
+
+    let (out, err) = utils::run(
+        "../fixtures/normal",
+        &["ws", "rename", "--dry-run", "ws_%n"],
+    );
+    assert_snapshot!(out);
+    assert_snapshot!(err);
+
+    assert_eq!(read_to_string(manifest).unwrap(), before);
+}
+
+#[test]
+fn test_events() {
+    let dir = utils::git_fixture("normal", "rename-events");
+    let events = |event: &str| {
+        ["top/", "dep1/", "dep2/", ""]
+            .iter()
+            .map(|x| {
+                format!(
+                    r#"{{"event":"{}","path":"{}/{}Cargo.toml"}}"#,
+                    event, dir, x
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+            + "\n"
+    };
+
+    let (out, _) = utils::run(
+        &dir,
+        &[
+            "ws",
+            "--message-format",
+            "json",
+            "rename",
+            "--dry-run",
+            "ws_%n",
+        ],
+    );
+    assert_eq!(out, events("manifest-would-write"));
+
+    let (out, _) = utils::run(&dir, &["ws", "--message-format", "json", "rename", "ws_%n"]);
+    assert_eq!(out, events("manifest-written"));
+
+    remove_dir_all(&dir).unwrap();
+}
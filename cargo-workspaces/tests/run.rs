@@ -0,0 +1,23 @@
+mod utils;
+#[cfg(not(windows))]
+use insta::assert_snapshot;
+
+#[cfg(not(windows))]
+#[test]
+fn test_defaults() {
+    let (out, _) = utils::run("../fixtures/scripts", &["ws", "run", "hello"]);
+    assert_snapshot!(out);
+}
+
+#[cfg(not(windows))]
+#[test]
+fn test_args() {
+    let (out, _) = utils::run("../fixtures/scripts", &["ws", "run", "lint", "--", "--fix"]);
+    assert_snapshot!(out);
+}
+
+#[test]
+fn test_not_found() {
+    let err = utils::run_err("../fixtures/scripts", &["ws", "run", "missing"]);
+    assert_eq!(err, "error: no crate defines the script missing\n");
+}
@@ -1,6 +1,14 @@
 #![allow(dead_code)]
 use assert_cmd::Command;
-use std::str::from_utf8;
+use std::{
+    env, fs,
+    io::{BufRead, BufReader, Write},
+    net::TcpListener,
+    path::Path,
+    process::{self, Command as StdCommand},
+    str::from_utf8,
+    thread,
+};
 
 pub fn run(dir: &str, args: &[&str]) -> (String, String) {
     let output = Command::cargo_bin("cargo-ws")
@@ -29,3 +37,99 @@ pub fn run_err(dir: &str, args: &[&str]) -> String {
     assert!(out.is_empty());
     err
 }
+
+/// Copy of the fixture in a new git repository, whose first commit is tagged `v0.1.0`
+pub fn git_fixture(fixture: &str, name: &str) -> String {
+    let dir = env::temp_dir().join(format!("cargo-ws-{}-{}", name, process::id()));
+    let _ = fs::remove_dir_all(&dir);
+
+    copy_dir(&Path::new("../fixtures").join(fixture), &dir);
+
+    let dir = dir.to_str().unwrap().to_string();
+
+    git(&dir, &["init", "-q", "-b", "master"]);
+    git(&dir, &["config", "user.name", "test"]);
+    git(&dir, &["config", "user.email", "test@example.com"]);
+    git(&dir, &["config", "commit.gpgsign", "false"]);
+    git(&dir, &["config", "tag.gpgsign", "false"]);
+    commit(&dir, "init");
+    git(&dir, &["tag", "v0.1.0"]);
+
+    dir
+}
+
+pub fn git(dir: &str, args: &[&str]) -> String {
+    let output = StdCommand::new("git")
+        .current_dir(dir)
+        .args(args)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success(), "git {}", args.join(" "));
+    from_utf8(&output.stdout).unwrap().trim().to_string()
+}
+
+/// Commits all the changes in the repository
+pub fn commit(dir: &str, msg: &str) {
+    git(dir, &["add", "-A"]);
+    git(dir, &["commit", "-q", "-m", msg]);
+}
+
+/// Appends a line to the file, creating it if needed
+pub fn touch(dir: &str, file: &str) {
+    append(dir, file, "// changed\n");
+}
+
+/// Appends the text to the file, creating it if needed
+pub fn append(dir: &str, file: &str, text: &str) {
+    let path = Path::new(dir).join(file);
+
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+    let mut content = fs::read_to_string(&path).unwrap_or_default();
+    content.push_str(text);
+    fs::write(path, content).unwrap();
+}
+
+/// Serves a sparse registry index without any crates, returning its URL
+pub fn empty_index() -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("sparse+http://{}/", listener.local_addr().unwrap());
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut lines = BufReader::new(stream.try_clone().unwrap()).lines();
+
+            // Skip the request and its headers
+            while !lines.next().unwrap().unwrap().is_empty() {}
+
+            stream
+                .write_all(
+                    b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                )
+                .unwrap();
+        }
+    });
+
+    url
+}
+
+fn copy_dir(from: &Path, to: &Path) {
+    fs::create_dir_all(to).unwrap();
+
+    for entry in fs::read_dir(from).unwrap() {
+        let entry = entry.unwrap();
+        let path = entry.path();
+
+        if entry.file_name() == "target" {
+            continue;
+        }
+
+        if path.is_dir() {
+            copy_dir(&path, &to.join(entry.file_name()));
+        } else {
+            fs::copy(&path, to.join(entry.file_name())).unwrap();
+        }
+    }
+}
@@ -0,0 +1,156 @@
+mod utils;
+use std::fs;
+
+#[test]
+fn test_failed_push_keeps_existing_tags() {
+    let dir = utils::git_fixture("normal", "version-push");
+    let remote = format!("{}-remote.git", dir);
+
+    let _ = fs::remove_dir_all(&remote);
+    utils::git(&dir, &["init", "-q", "--bare", &remote]);
+    utils::git(&dir, &["remote", "add", "origin", &remote]);
+    utils::git(&dir, &["push", "-q", "origin", "master"]);
+
+    // The remote is gone by the time the release is pushed
+    fs::remove_dir_all(&remote).unwrap();
+
+    // Released before, with the version the release is going to use
+    utils::git(&dir, &["tag", "v0.1.1"]);
+
+    utils::touch(&dir, "dep1/src/lib.rs");
+    utils::commit(&dir, "change dep1");
+
+    let (_, err) = utils::run(&dir, &["ws", "version", "patch", "-y"]);
+    assert!(err.contains("removing tags dep1@0.1.1\n"));
+    assert!(err.contains("unable to push to remote"));
+
+    // Only the tag created by this run is rolled back
+    assert_eq!(utils::git(&dir, &["tag", "-l"]), "v0.1.0\nv0.1.1");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+/// Releases the crates of the normal fixture at 1.0.0, each with its own version and tag
+fn independent_fixture(name: &str) -> String {
+    let dir = utils::git_fixture("normal", name);
+
+    for pkg in ["dep1", "dep2", "top"] {
+        let manifest = format!("{}/{}/Cargo.toml", dir, pkg);
+        let content = fs::read_to_string(&manifest)
+            .unwrap()
+            .replace("0.1.0", "1.0.0");
+
+        fs::write(&manifest, content).unwrap();
+        utils::append(
+            &dir,
+            &format!("{}/Cargo.toml", pkg),
+            "\n[package.metadata.workspaces]\nindependent = true\n",
+        );
+    }
+
+    utils::commit(&dir, "release 1.0.0");
+
+    for pkg in ["dep1", "dep2", "top"] {
+        utils::git(&dir, &["tag", &format!("{}@1.0.0", pkg)]);
+    }
+
+    dir
+}
+
+/// Releases dep1 on its own as 1.0.1
+fn release_dep1(dir: &str) {
+    let manifest = format!("{}/dep1/Cargo.toml", dir);
+    let content = fs::read_to_string(&manifest).unwrap();
+
+    fs::write(&manifest, content.replacen("1.0.0", "1.0.1", 1)).unwrap();
+    utils::commit(dir, "release dep1");
+    utils::git(dir, &["tag", "dep1@1.0.1"]);
+}
+
+#[test]
+fn test_conventional_commits() {
+    let dir = independent_fixture("version-conventional");
+
+    utils::touch(&dir, "dep2/src/lib.rs");
+    utils::commit(&dir, "feat: add to dep2");
+
+    utils::touch(&dir, "dep1/src/lib.rs");
+    utils::commit(&dir, "feat!: rework dep1");
+
+    // Released on its own since then
+    release_dep1(&dir);
+
+    utils::touch(&dir, "dep1/src/lib.rs");
+    utils::commit(&dir, "fix: dep1");
+
+    utils::touch(&dir, "top/src/main.rs");
+    utils::commit(&dir, "refactor: top\n\nBREAKING CHANGE: no more main");
+
+    let (_, err) = utils::run(
+        &dir,
+        &[
+            "ws",
+            "version",
+            "--conventional-commits",
+            "-y",
+            "--no-git-push",
+        ],
+    );
+
+    // The breaking change of dep1 was released already, while the feature of dep2 was not
+    assert!(err.contains(" - dep1: 1.0.1 => 1.0.2\n"));
+    assert!(err.contains(" - dep2: 1.0.0 => 1.1.0\n"));
+    assert!(err.contains(" - top: 1.0.0 => 2.0.0\n"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_changelog() {
+    let dir = independent_fixture("version-changelog");
+
+    utils::touch(&dir, "dep2/src/lib.rs");
+    utils::commit(&dir, "feat: add to dep2");
+
+    utils::touch(&dir, "dep1/src/lib.rs");
+    utils::commit(&dir, "feat: add to dep1");
+    release_dep1(&dir);
+
+    utils::touch(&dir, "dep1/src/lib.rs");
+    utils::commit(&dir, "fix: dep1");
+
+    utils::run(
+        &dir,
+        &[
+            "ws",
+            "version",
+            "patch",
+            "--changelog",
+            "--workspace-changelog",
+            "-y",
+            "--no-git-push",
+        ],
+    );
+
+    // Each crate gets the commits since its own release
+    let changelog = |path: &str| fs::read_to_string(format!("{}/{}", dir, path)).unwrap();
+
+    assert_eq!(
+        changelog("dep1/CHANGELOG.md"),
+        "# Changelog\n\n## 1.0.2\n\n- fix: dep1\n"
+    );
+    assert_eq!(
+        changelog("dep2/CHANGELOG.md"),
+        "# Changelog\n\n## 1.0.1\n\n- feat: add to dep2\n"
+    );
+    assert!(changelog("CHANGELOG.md").contains(
+        "### default\n\n- dep1@1.0.2\n  - fix: dep1\n- dep2@1.0.1\n  - feat: add to dep2\n"
+    ));
+
+    // The changelogs are part of the release commit
+    let files = utils::git(&dir, &["show", "--name-only", "--format=", "HEAD"]);
+    assert!(files.contains("CHANGELOG.md\ndep1/CHANGELOG.md\n"));
+    assert!(files.contains("dep2/CHANGELOG.md\n"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}